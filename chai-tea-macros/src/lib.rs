@@ -0,0 +1,503 @@
+//! The `#[chai_app]` attribute for [chai-tea](https://docs.rs/chai-tea).
+//!
+//! Use it through chai-tea's `macros` feature rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{
+    Error, Expr, FnArg, ImplItem, Item, ItemImpl, ItemMod, LitStr, ReturnType, Signature, Type,
+    Visibility, parse_macro_input,
+};
+
+/// Generate `main` for a chai-tea app from the functions in a module or `impl` block.
+///
+/// The annotated item must contain `init`, `update` and `view`, and may contain `run_cmd` and
+/// `sync_state_init`. What they return picks the entry point:
+///
+/// - `init() -> Model` makes a plain app, run with `brew`.
+/// - `init() -> (Model, commands)` makes an app with commands, run with `brew_async`.
+///   `run_cmd` defaults to `chai_tea::no_run_cmd` and `sync_state_init` to `|| ()`.
+/// - `update(&mut Model, Msg)` changes the model in place, run through `App::builder` with
+///   `.update_mut`.
+///
+/// Arguments, all optional:
+///
+/// - `title = "..."`: the window title. Defaults to the module or type name.
+/// - `options = expr`: the `eframe::NativeOptions` to open the window with.
+///
+/// Functions in a module must be `pub`, since `main` is generated next to the module.
+/// Signatures are checked before anything is generated, and mistakes are reported on the
+/// function that has them.
+///
+/// # Example
+/// ```no_run
+/// #[chai_tea::chai_app(title = "counter")]
+/// mod counter {
+///     use eframe::egui;
+///
+///     pub struct Model { pub count: i32 }
+///     pub enum Msg { Inc }
+///
+///     pub fn init() -> Model {
+///         Model { count: 0 }
+///     }
+///
+///     pub fn update(m: Model, msg: Msg) -> Model {
+///         match msg {
+///             Msg::Inc => Model { count: m.count + 1 },
+///         }
+///     }
+///
+///     pub fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) {
+///         egui::CentralPanel::default().show(ctx, |ui| {
+///             if ui.button(m.count.to_string()).clicked() {
+///                 tx.push(Msg::Inc);
+///             }
+///         });
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn chai_app(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut args = Args::default();
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("title") {
+            args.title = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("options") {
+            args.options = Some(meta.value()?.parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `title = \"...\"` or `options = ...`"))
+        }
+    });
+    parse_macro_input!(attr with parser);
+    let item = parse_macro_input!(item as Item);
+
+    match expand(args, &item) {
+        Ok(expanded) => expanded.into(),
+        Err(err) => err.into_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct Args {
+    title: Option<LitStr>,
+    options: Option<Expr>,
+}
+
+/// The app's functions, found by name.
+struct Functions<'a> {
+    init: &'a Signature,
+    update: &'a Signature,
+    view: &'a Signature,
+    run_cmd: Option<&'a Signature>,
+    sync_state_init: Option<&'a Signature>,
+}
+
+impl<'a> Functions<'a> {
+    fn find(
+        fns: &[(&'a Visibility, &'a Signature)],
+        owner: &dyn quote::ToTokens,
+        in_module: bool,
+    ) -> syn::Result<Self> {
+        let get = |name: &str| -> syn::Result<Option<&'a Signature>> {
+            let Some((vis, sig)) = fns.iter().find(|(_, sig)| sig.ident == name) else {
+                return Ok(None);
+            };
+            if in_module && matches!(vis, Visibility::Inherited) {
+                return Err(Error::new_spanned(
+                    &sig.ident,
+                    format!("`{name}` must be `pub` so the generated `main` can call it"),
+                ));
+            }
+            check_plain(sig)?;
+            Ok(Some(sig))
+        };
+        let required = |name: &str| -> syn::Result<&'a Signature> {
+            get(name)?.ok_or_else(|| {
+                Error::new_spanned(owner, format!("`#[chai_app]` needs a `{name}` function"))
+            })
+        };
+
+        Ok(Self {
+            init: required("init")?,
+            update: required("update")?,
+            view: required("view")?,
+            run_cmd: get("run_cmd")?,
+            sync_state_init: get("sync_state_init")?,
+        })
+    }
+}
+
+fn expand(args: Args, item: &Item) -> syn::Result<TokenStream2> {
+    match item {
+        Item::Mod(module) => expand_mod(args, module.clone()),
+        Item::Impl(block) => expand_impl(args, block),
+        item => Err(Error::new_spanned(
+            item,
+            "`#[chai_app]` goes on a module or an `impl` block holding `init`, `update` and `view`",
+        )),
+    }
+}
+
+fn expand_mod(args: Args, mut module: ItemMod) -> syn::Result<TokenStream2> {
+    let Some((_, items)) = &module.content else {
+        return Err(Error::new_spanned(
+            &module,
+            "`#[chai_app]` needs the module's contents inline",
+        ));
+    };
+    let fns: Vec<_> = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Fn(f) => Some((&f.vis, &f.sig)),
+            _ => None,
+        })
+        .collect();
+
+    let ident = &module.ident;
+    let title = args
+        .title
+        .clone()
+        .unwrap_or_else(|| LitStr::new(&ident.to_string(), ident.span()));
+    let functions = Functions::find(&fns, ident, true)?;
+    let app = App::new(&functions)?;
+
+    let checks = app.checks(&functions, false);
+    let main = app.main(&functions, &quote!(#ident::), &title, args.options.as_ref());
+
+    if let Some((_, items)) = &mut module.content {
+        items.push(syn::parse_quote! {
+            #[allow(dead_code)]
+            fn __chai_app_signatures() {
+                #checks
+            }
+        });
+    }
+    Ok(quote! {
+        #module
+        #main
+    })
+}
+
+fn expand_impl(args: Args, block: &ItemImpl) -> syn::Result<TokenStream2> {
+    if let Some((_, trait_path, _)) = &block.trait_ {
+        return Err(Error::new_spanned(
+            trait_path,
+            "`#[chai_app]` goes on an inherent `impl` block, not a trait implementation",
+        ));
+    }
+    if !block.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &block.generics,
+            "`#[chai_app]` can't run a generic type; name the concrete types",
+        ));
+    }
+    let fns: Vec<_> = block
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ImplItem::Fn(f) => Some((&f.vis, &f.sig)),
+            _ => None,
+        })
+        .collect();
+
+    let self_ty = &block.self_ty;
+    let title = args.title.clone().unwrap_or_else(|| {
+        let name = match &**self_ty {
+            Type::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        };
+        LitStr::new(name.as_deref().unwrap_or("chai-tea"), self_ty.span())
+    });
+    let functions = Functions::find(&fns, self_ty, false)?;
+    let app = App::new(&functions)?;
+
+    let checks = app.checks(&functions, true);
+    let main = app.main(
+        &functions,
+        &quote!(<#self_ty>::),
+        &title,
+        args.options.as_ref(),
+    );
+
+    Ok(quote! {
+        #block
+
+        impl #self_ty {
+            #[allow(dead_code)]
+            fn __chai_app_signatures() {
+                #checks
+            }
+        }
+
+        #main
+    })
+}
+
+/// What the signatures say about the app.
+struct App<'a> {
+    model: &'a Type,
+    msg: &'a Type,
+    /// `init` returns commands, so the app is run with `brew_async`.
+    with_cmds: bool,
+    /// `update` takes `&mut Model`.
+    in_place: bool,
+}
+
+impl<'a> App<'a> {
+    fn new(functions: &Functions<'a>) -> syn::Result<Self> {
+        let Functions {
+            init,
+            update,
+            view,
+            run_cmd,
+            sync_state_init,
+        } = functions;
+
+        check_arity(init, 0, "no parameters")?;
+        let ReturnType::Type(_, init_ret) = &init.output else {
+            return Err(Error::new_spanned(
+                &init.ident,
+                "`init` must return the initial model, or `(model, commands)` for an app with commands",
+            ));
+        };
+        let (model, with_cmds) = match pair(init_ret) {
+            Some((model, _)) => (model, true),
+            None => (&**init_ret, false),
+        };
+
+        let [first, msg] = check_arity(update, 2, "`(model, msg)`")?[..] else {
+            unreachable!()
+        };
+        let in_place = matches!(first, Type::Reference(r) if r.mutability.is_some());
+        if !with_cmds && in_place && update.output != ReturnType::Default {
+            return Err(Error::new_spanned(
+                &update.output,
+                "`update` returns commands, but `init` doesn't; return `(model, commands)` from `init` too",
+            ));
+        }
+        let returned_pair = match &update.output {
+            ReturnType::Type(_, ty) => pair(ty),
+            ReturnType::Default => None,
+        };
+        let returns_model_and_cmds = returned_pair.is_some_and(|(first, _)| same(first, model));
+        if !with_cmds && !in_place && returns_model_and_cmds {
+            return Err(Error::new_spanned(
+                &update.output,
+                "`update` returns commands, but `init` doesn't; return `(model, commands)` from `init` too",
+            ));
+        }
+        if with_cmds && !in_place && returned_pair.is_none() {
+            return Err(Error::new_spanned(
+                &update.output,
+                "`init` returns commands, so `update` must return `(model, commands)` too",
+            ));
+        }
+
+        check_arity(view, 3, "`(ctx, &model, tx)`")?;
+        if let Some(run_cmd) = run_cmd {
+            check_arity(run_cmd, 3, "`(cmd, &mut sync_state, tx)`")?;
+        }
+        if let Some(sync_state_init) = sync_state_init {
+            check_arity(sync_state_init, 0, "no parameters")?;
+        }
+        if !with_cmds && let Some(sig) = run_cmd.or(*sync_state_init) {
+            return Err(Error::new_spanned(
+                &sig.ident,
+                format!(
+                    "`{}` is only used by apps with commands; return `(model, commands)` from `init`",
+                    sig.ident
+                ),
+            ));
+        }
+
+        Ok(Self {
+            model,
+            msg,
+            with_cmds,
+            in_place,
+        })
+    }
+
+    /// Coercions to the expected fn pointer types, so a mismatch is reported on the function.
+    ///
+    /// `in_impl` says the functions are associated ones, reached through `Self`.
+    fn checks(&self, functions: &Functions, in_impl: bool) -> TokenStream2 {
+        let Self { model, msg, .. } = self;
+        let check = |sig: &Signature, ty: TokenStream2| {
+            let ident = &sig.ident;
+            let prefix = in_impl.then(|| quote_spanned!(ident.span()=> Self::));
+            quote_spanned! {ident.span()=>
+                let _: #ty = #prefix #ident;
+            }
+        };
+
+        let update_ty = match (self.in_place, self.with_cmds) {
+            (true, true) => quote!(fn(&mut #model, #msg) -> _),
+            (true, false) => quote!(fn(&mut #model, #msg)),
+            (false, true) => quote!(fn(#model, #msg) -> (#model, _)),
+            (false, false) => quote!(fn(#model, #msg) -> #model),
+        };
+        let mut checks = vec![
+            check(functions.update, update_ty),
+            check(
+                functions.view,
+                quote!(fn(&_, &#model, &mut ::std::vec::Vec<#msg>)),
+            ),
+        ];
+        if let Some(run_cmd) = functions.run_cmd {
+            checks.push(check(
+                run_cmd,
+                quote!(fn(_, &mut _, ::chai_tea::ChaiSender<#msg>)),
+            ));
+        }
+        quote!(#(#checks)*)
+    }
+
+    fn main(
+        &self,
+        functions: &Functions,
+        prefix: &TokenStream2,
+        title: &LitStr,
+        options: Option<&Expr>,
+    ) -> TokenStream2 {
+        let init = &functions.init.ident;
+        let update = &functions.update.ident;
+        let view = &functions.view.ident;
+        let (init, update, view) = (
+            quote!(#prefix #init),
+            quote!(#prefix #update),
+            quote!(#prefix #view),
+        );
+        let run_cmd = match functions.run_cmd {
+            Some(sig) => {
+                let ident = &sig.ident;
+                quote!(#prefix #ident)
+            }
+            None => quote!(::chai_tea::no_run_cmd),
+        };
+        let sync_state_init = match functions.sync_state_init {
+            Some(sig) => {
+                let ident = &sig.ident;
+                quote!(#prefix #ident)
+            }
+            None => quote!(|| ()),
+        };
+
+        let body = match (options, self.with_cmds, self.in_place) {
+            (None, false, false) => quote!(::chai_tea::brew(#title, #init, #update, #view)),
+            (Some(options), false, false) => quote! {
+                ::chai_tea::brew_with_opts(#title, #options, #init, #update, #view)
+            },
+            (None, true, false) => quote! {
+                ::chai_tea::brew_async(#title, #init, #sync_state_init, #update, #view, #run_cmd)
+            },
+            (options, with_cmds, in_place) => {
+                let init = if with_cmds {
+                    init
+                } else {
+                    quote!(|| (#init(), ::chai_tea::Cmd::<_>::none()))
+                };
+                let update = if in_place {
+                    quote!(.update_mut(#update))
+                } else {
+                    quote!(.update(#update))
+                };
+                let options = options.map(|options| quote!(.options(#options)));
+                // without commands nothing crosses threads, so messages needn't be `Send`
+                let run = if with_cmds {
+                    quote!(.run())
+                } else {
+                    quote!(.run_local())
+                };
+                quote! {
+                    ::chai_tea::App::builder(#title)
+                        .sync_state(#sync_state_init)
+                        .init(#init)
+                        #update
+                        .view(#view)
+                        .run_cmd(#run_cmd)
+                        #options
+                        #run
+                }
+            }
+        };
+
+        quote! {
+            fn main() -> ::chai_tea::__private::eframe::Result<()> {
+                #body
+            }
+        }
+    }
+}
+
+/// Reject signatures the runtime can't call as a plain function.
+fn check_plain(sig: &Signature) -> syn::Result<()> {
+    let name = &sig.ident;
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(Error::new_spanned(
+            asyncness,
+            format!("`{name}` can't be async; start async work with a command instead"),
+        ));
+    }
+    if let Some(receiver) = sig.receiver() {
+        return Err(Error::new_spanned(
+            receiver,
+            format!("`{name}` can't take `self`; the model is passed in as a parameter"),
+        ));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &sig.generics,
+            format!("`{name}` can't be generic; chai-tea needs the concrete types"),
+        ));
+    }
+    Ok(())
+}
+
+/// The parameter types, if there are `count` of them.
+fn check_arity<'a>(sig: &'a Signature, count: usize, shape: &str) -> syn::Result<Vec<&'a Type>> {
+    let types: Vec<_> = sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(arg) => Some(&*arg.ty),
+            FnArg::Receiver(_) => None,
+        })
+        .collect();
+    if types.len() == count {
+        return Ok(types);
+    }
+    let span = if sig.inputs.is_empty() {
+        sig.ident.span()
+    } else {
+        sig.inputs.span()
+    };
+    Err(Error::new(
+        span,
+        format!(
+            "`{}` must take {shape}, found {} parameter{}",
+            sig.ident,
+            types.len(),
+            if types.len() == 1 { "" } else { "s" }
+        ),
+    ))
+}
+
+/// Whether two types are written the same way.
+fn same(a: &Type, b: &Type) -> bool {
+    quote!(#a).to_string() == quote!(#b).to_string()
+}
+
+/// The two halves of a `(model, commands)` pair.
+fn pair(ty: &Type) -> Option<(&Type, &Type)> {
+    match ty {
+        Type::Tuple(tuple) if tuple.elems.len() == 2 => Some((&tuple.elems[0], &tuple.elems[1])),
+        Type::Paren(inner) => pair(&inner.elem),
+        _ => None,
+    }
+}
@@ -0,0 +1,6 @@
+//! Signature mistakes `#[chai_app]` reports, pinned to their messages and spans.
+
+#[test]
+fn signature_mistakes() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
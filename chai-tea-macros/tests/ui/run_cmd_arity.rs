@@ -0,0 +1,21 @@
+#[chai_tea::chai_app]
+mod counter {
+    use eframe::egui;
+
+    pub struct Model;
+    pub enum Msg {}
+
+    pub enum Cmd {}
+
+    pub fn init() -> (Model, Vec<Cmd>) {
+        (Model, vec![])
+    }
+
+    pub fn update(m: Model, _: Msg) -> (Model, Vec<Cmd>) {
+        (m, vec![])
+    }
+
+    pub fn view(_: &egui::Context, _: &Model, _: &mut Vec<Msg>) {}
+
+    pub fn run_cmd(_: Cmd, _: &mut ()) {}
+}
@@ -0,0 +1,16 @@
+struct Counter;
+struct Model;
+enum Msg {}
+
+#[chai_tea::chai_app]
+impl Counter {
+    fn init() -> Model {
+        Model
+    }
+
+    fn update(self, _: Msg) -> Model {
+        Model
+    }
+
+    fn view(_: &eframe::egui::Context, _: &Model, _: &mut Vec<Msg>) {}
+}
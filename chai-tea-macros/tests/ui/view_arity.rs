@@ -0,0 +1,17 @@
+#[chai_tea::chai_app]
+mod counter {
+    use eframe::egui;
+
+    pub struct Model;
+    pub enum Msg {}
+
+    pub fn init() -> Model {
+        Model
+    }
+
+    pub fn update(m: Model, _: Msg) -> Model {
+        m
+    }
+
+    pub fn view(_: &egui::Context, _: &Model) {}
+}
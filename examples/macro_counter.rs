@@ -0,0 +1,42 @@
+// The counter example, with `main` generated by `#[chai_app]`.
+// `cargo run --example macro_counter --features macros`
+
+#[chai_tea::chai_app(title = "chai_app")]
+mod counter {
+    use eframe::egui;
+
+    pub struct Model {
+        counter: i32,
+    }
+
+    pub enum Msg {
+        Increment,
+        Decrement,
+    }
+
+    pub fn init() -> Model {
+        Model { counter: 0 }
+    }
+
+    pub fn update(model: &mut Model, msg: Msg) {
+        match msg {
+            Msg::Increment => model.counter += 1,
+            Msg::Decrement => model.counter -= 1,
+        }
+    }
+
+    pub fn view(ctx: &egui::Context, model: &Model, tx: &mut Vec<Msg>) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading("Chai Tea");
+            ui.label(format!("value: {}", model.counter));
+            ui.horizontal(|ui| {
+                if ui.button("+").clicked() {
+                    tx.push(Msg::Increment);
+                }
+                if ui.button("-").clicked() {
+                    tx.push(Msg::Decrement);
+                }
+            });
+        });
+    }
+}
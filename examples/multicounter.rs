@@ -1,242 +1,157 @@
+use chai_tea::{Cmd, Component, Dispatch, Embed};
 use eframe::egui;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-#[derive(Default)]
-struct Model {
-    counter1: i64,
-    counter2: i64,
-    counter1_up: bool,
-    counter2_up: bool,
+// One counter, knowing nothing about the app it is embedded in.
+struct Counter;
 
-    counter1_enabled: bool,
-    counter2_enabled: bool,
+struct CounterModel {
+    count: i64,
+    count_up: bool,
+    enabled: bool,
+    period: Duration,
 }
 
-enum Msg {
-    Count1(i64),
-    Count2(i64),
-    Start1,
-    Stop1,
-    Start2,
-    Stop2,
-    CountDir1(bool),
-    CountDir2(bool),
+impl CounterModel {
+    fn new(period: Duration) -> Self {
+        Self {
+            count: 0,
+            count_up: true,
+            enabled: false,
+            period,
+        }
+    }
 }
 
-fn init() -> Model {
-    Model {
-        counter1_up: true,
-        counter2_up: true,
-        ..Default::default()
+impl Default for CounterModel {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(1))
     }
 }
 
-fn update(model: Model, msg: Msg) -> (Model, Option<Cmd>) {
-    match msg {
-        Msg::Count1(val) => (
-            Model {
-                counter1: val,
-                ..model
-            },
-            None,
-        ),
-        Msg::Count2(val) => (
-            Model {
-                counter2: val,
-                ..model
-            },
-            None,
-        ),
-        Msg::Start1 => (
-            Model {
-                counter1_enabled: true,
-                ..model
-            },
-            Some(Cmd::Start1),
-        ),
-        Msg::Start2 => (
-            Model {
-                counter2_enabled: true,
-                ..model
-            },
-            Some(Cmd::Start2),
-        ),
-        Msg::Stop1 => (
-            Model {
-                counter1: 0,
-                counter1_enabled: false,
-                ..model
-            },
-            Some(Cmd::Stop1),
-        ),
-        Msg::Stop2 => (
-            Model {
-                counter2: 0,
-                counter2_enabled: false,
-                ..model
-            },
-            Some(Cmd::Stop2),
-        ),
-        Msg::CountDir1(val) => (
-            Model {
-                counter1_up: val,
-                ..model
-            },
-            Some(Cmd::CountDir1(val)),
-        ),
-        Msg::CountDir2(val) => (
-            Model {
-                counter2_up: val,
-                ..model
-            },
-            Some(Cmd::CountDir2(val)),
-        ),
-    }
+enum CounterMsg {
+    Start,
+    Stop,
+    Tick,
+    CountDir(bool),
 }
 
-fn view(ctx: &egui::Context, model: &Model, tx: &mut Vec<Msg>) {
-    egui::CentralPanel::default().show(ctx, |ui| {
-        ui.heading("Chai Double Counter");
-        ui.horizontal(|ui| {
-            ui.vertical(|ui| {
-                ui.label(format!("{}", model.counter1));
-                if model.counter1_enabled {
-                    if ui.button("stop").clicked() {
-                        tx.push(Msg::Stop1);
-                    }
-                } else if ui.button("start").clicked() {
-                    tx.push(Msg::Start1);
-                }
+// Arrives after one period; keyed, so `Stop` cancels it and the tick never arrives.
+fn tick(period: Duration) -> Cmd<CounterMsg> {
+    Cmd::after(period, CounterMsg::Tick).with_key("tick")
+}
 
-                let mut up = model.counter1_up;
-                if ui.checkbox(&mut up, "count up").changed() {
-                    tx.push(Msg::CountDir1(up));
-                }
-            });
-
-            ui.vertical(|ui| {
-                ui.label(format!("{}", model.counter2));
-                if model.counter2_enabled {
-                    if ui.button("stop").clicked() {
-                        tx.push(Msg::Stop2);
-                    }
-                } else if ui.button("start").clicked() {
-                    tx.push(Msg::Start2);
-                }
-                let mut up = model.counter2_up;
-                if ui.checkbox(&mut up, "count up").changed() {
-                    tx.push(Msg::CountDir2(up));
+impl Component for Counter {
+    type Model = CounterModel;
+    type Msg = CounterMsg;
+
+    fn update(model: CounterModel, msg: CounterMsg) -> (CounterModel, Cmd<CounterMsg>) {
+        match msg {
+            CounterMsg::Start => (
+                CounterModel {
+                    enabled: true,
+                    ..model
+                },
+                tick(model.period),
+            ),
+            CounterMsg::Stop => (
+                CounterModel {
+                    count: 0,
+                    enabled: false,
+                    ..model
+                },
+                Cmd::cancel("tick"),
+            ),
+            CounterMsg::Tick => {
+                let step = if model.count_up { 1 } else { -1 };
+                (
+                    CounterModel {
+                        count: model.count + step,
+                        ..model
+                    },
+                    tick(model.period),
+                )
+            }
+            CounterMsg::CountDir(count_up) => (CounterModel { count_up, ..model }, Cmd::none()),
+        }
+    }
+
+    fn view(model: &CounterModel, ui: &mut egui::Ui, tx: &mut dyn Dispatch<CounterMsg>) {
+        ui.vertical(|ui| {
+            ui.label(format!("{}", model.count));
+            if model.enabled {
+                if ui.button("stop").clicked() {
+                    tx.push(CounterMsg::Stop);
                 }
-            });
+            } else if ui.button("start").clicked() {
+                tx.push(CounterMsg::Start);
+            }
+
+            let mut up = model.count_up;
+            if ui.checkbox(&mut up, "count up").changed() {
+                tx.push(CounterMsg::CountDir(up));
+            }
         });
-    });
+    }
+}
+
+// The app: two instances of the same counter.
+#[derive(Default)]
+struct Model {
+    counter1: CounterModel,
+    counter2: CounterModel,
 }
 
-struct SyncState {
-    count_up_flag1: Arc<AtomicBool>,
-    count_up_flag2: Arc<AtomicBool>,
-    stop_flag1: Arc<AtomicBool>,
-    stop_flag2: Arc<AtomicBool>,
+enum Msg {
+    Counter1(CounterMsg),
+    Counter2(CounterMsg),
 }
 
-enum Cmd {
-    Start1,
-    Start2,
-    Stop1,
-    Stop2,
-    CountDir1(bool),
-    CountDir2(bool),
+fn counter1() -> Embed<Counter, Msg> {
+    Embed::new("counter1", Msg::Counter1)
 }
 
-fn sync_state_init() -> SyncState {
-    SyncState {
-        count_up_flag1: Arc::new(AtomicBool::new(true)),
-        count_up_flag2: Arc::new(AtomicBool::new(true)),
-        stop_flag1: Arc::new(AtomicBool::new(false)),
-        stop_flag2: Arc::new(AtomicBool::new(false)),
-    }
+fn counter2() -> Embed<Counter, Msg> {
+    Embed::new("counter2", Msg::Counter2)
 }
 
-fn run_cmd(cmd: Cmd, sync_state: &mut SyncState, tx: chai_tea::ChaiSender<Msg>) {
-    match cmd {
-        Cmd::Start1 => {
-            sync_state.stop_flag1.store(false, Ordering::SeqCst);
-            let flag = sync_state.stop_flag1.clone();
-            let count_up_flag = sync_state.count_up_flag1.clone();
-
-            std::thread::spawn(move || {
-                let mut counter: i64 = 0;
-                loop {
-                    if count_up_flag.load(Ordering::SeqCst) {
-                        counter += 1;
-                    } else {
-                        counter -= 1;
-                    }
-
-                    if !interruptible_sleep(std::time::Duration::from_millis(300), &flag) {
-                        return;
-                    }
-
-                    if tx.send(Msg::Count1(counter)).is_err() {
-                        return;
-                    }
-                }
-            });
-        }
+fn init() -> (Model, Cmd<Msg>) {
+    (
+        Model {
+            counter1: CounterModel::new(Duration::from_millis(300)),
+            counter2: CounterModel::new(Duration::from_millis(1000)),
+        },
+        Cmd::none(),
+    )
+}
 
-        Cmd::Start2 => {
-            sync_state.stop_flag2.store(false, Ordering::SeqCst);
-            let flag = sync_state.stop_flag2.clone();
-            let count_up_flag = sync_state.count_up_flag2.clone();
-
-            std::thread::spawn(move || {
-                let mut counter: i64 = 0;
-                loop {
-                    if count_up_flag.load(Ordering::SeqCst) {
-                        counter += 1;
-                    } else {
-                        counter -= 1;
-                    }
-
-                    if !interruptible_sleep(std::time::Duration::from_millis(1000), &flag) {
-                        return;
-                    }
-
-                    if tx.send(Msg::Count2(counter)).is_err() {
-                        return;
-                    }
-                }
-            });
+fn update(model: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+    match msg {
+        Msg::Counter1(msg) => {
+            let (counter1, cmd) = counter1().update(model.counter1, msg);
+            (Model { counter1, ..model }, cmd)
+        }
+        Msg::Counter2(msg) => {
+            let (counter2, cmd) = counter2().update(model.counter2, msg);
+            (Model { counter2, ..model }, cmd)
         }
-
-        Cmd::Stop1 => sync_state.stop_flag1.store(true, Ordering::SeqCst),
-        Cmd::Stop2 => sync_state.stop_flag2.store(true, Ordering::SeqCst),
-
-        Cmd::CountDir1(val) => sync_state.count_up_flag1.store(val, Ordering::SeqCst),
-        Cmd::CountDir2(val) => sync_state.count_up_flag2.store(val, Ordering::SeqCst),
     }
 }
 
-fn interruptible_sleep(total: std::time::Duration, flag: &AtomicBool) -> bool {
-    let step = std::time::Duration::from_millis(10);
-    let start = std::time::Instant::now();
-    while std::time::Instant::now() - start < total {
-        if flag.load(Ordering::SeqCst) {
-            return false; // interrupted
-        }
-        std::thread::sleep(step);
-    }
-    true // completed
+fn view(ctx: &egui::Context, model: &Model, tx: &mut Vec<Msg>) {
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.heading("Chai Double Counter");
+        ui.horizontal(|ui| {
+            counter1().view(&model.counter1, ui, tx);
+            counter2().view(&model.counter2, ui, tx);
+        });
+    });
 }
 
 fn main() -> Result<(), eframe::Error> {
-    chai_tea::brew_async(
-        "chai_counters",
-        init,
-        sync_state_init,
-        update,
-        view,
-        run_cmd,
-    )
+    chai_tea::App::builder("chai_counters")
+        .init(init)
+        .update(update)
+        .view(view)
+        .run()
 }
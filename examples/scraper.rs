@@ -1,3 +1,4 @@
+use chai_tea::Cmd;
 use eframe::egui;
 use scraper::{Html, Selector};
 
@@ -9,22 +10,32 @@ struct Model {
 enum Msg {
     GetCountries,
     CountryList(Vec<String>),
+    FetchFailed(String),
 }
 
-fn init() -> Model {
-    Model::default()
+fn init() -> (Model, Cmd<Msg>) {
+    (Model::default(), Cmd::none())
 }
 
-fn update(_model: Model, msg: Msg) -> (Model, Option<Cmd>) {
+fn update(_model: Model, msg: Msg) -> (Model, Cmd<Msg>) {
     match msg {
         Msg::GetCountries => (
             Model {
                 countries: vec!["Loading...".into()],
             },
-            Some(Cmd::GetCountries),
+            Cmd::attempt(get_countries(), Msg::CountryList, |err| {
+                Msg::FetchFailed(err.to_string())
+            }),
         ),
 
-        Msg::CountryList(list) => (Model { countries: list }, None),
+        Msg::CountryList(list) => (Model { countries: list }, Cmd::none()),
+
+        Msg::FetchFailed(err) => (
+            Model {
+                countries: vec![format!("Failed: {err}")],
+            },
+            Cmd::none(),
+        ),
     }
 }
 
@@ -45,45 +56,35 @@ fn view(ctx: &egui::Context, model: &Model, tx: &mut Vec<Msg>) {
     });
 }
 
-struct SyncState {}
-
-enum Cmd {
-    GetCountries,
-}
-
-fn sync_state_init() -> SyncState {
-    SyncState {}
-}
-
-fn run_cmd(cmd: Cmd, _sync_state: &mut SyncState, tx: chai_tea::ChaiSender<Msg>) {
-    match cmd {
-        Cmd::GetCountries => {
-            tokio::spawn(async move {
-                let url = "https://www.scrapethissite.com/pages/simple/";
-                let response = reqwest::get(url).await.unwrap().text().await.unwrap();
-                let document = Html::parse_document(&response);
-                let selector = Selector::parse("h3").unwrap();
+async fn get_countries() -> Result<Vec<String>, reqwest::Error> {
+    let url = "https://www.scrapethissite.com/pages/simple/";
+    let response = reqwest::get(url).await?.text().await?;
+    let document = Html::parse_document(&response);
+    let selector = Selector::parse("h3").unwrap();
 
-                let elements: Vec<_> = document
-                    .select(&selector)
-                    .map(|x| {
-                        x.text()
-                            .collect::<Vec<_>>()
-                            .concat()
-                            .chars()
-                            .filter(|c| *c != '\n')
-                            .collect::<String>()
-                    })
-                    .map(|x| x.trim().to_string())
-                    .collect();
+    let elements = document
+        .select(&selector)
+        .map(|x| {
+            x.text()
+                .collect::<Vec<_>>()
+                .concat()
+                .chars()
+                .filter(|c| *c != '\n')
+                .collect::<String>()
+        })
+        .map(|x| x.trim().to_string())
+        .collect();
 
-                tx.send(Msg::CountryList(elements)).ok();
-            });
-        }
-    }
+    Ok(elements)
 }
 
-#[tokio::main]
-async fn main() -> Result<(), eframe::Error> {
-    chai_tea::brew_async("chai_scraper", init, sync_state_init, update, view, run_cmd)
+fn main() -> Result<(), eframe::Error> {
+    chai_tea::brew_async(
+        "chai_scraper",
+        init,
+        || (),
+        update,
+        view,
+        chai_tea::no_run_cmd,
+    )
 }
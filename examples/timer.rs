@@ -1,6 +1,5 @@
+use chai_tea::{Cmd, Sub, WindowProps};
 use eframe::egui;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
 
 struct Model {
     total_time: u64,
@@ -15,7 +14,7 @@ enum State {
 
 enum Msg {
     NewTime(String),
-    Tick(u64),
+    Tick,
     Start,
     Stop,
 }
@@ -31,11 +30,11 @@ impl Default for Model {
     }
 }
 
-fn init() -> Model {
-    Model::default()
+fn init() -> (Model, Cmd<Msg>) {
+    (Model::default(), Cmd::none())
 }
 
-fn update(model: Model, msg: Msg) -> (Model, Option<Cmd>) {
+fn update(model: Model, msg: Msg) -> (Model, Cmd<Msg>) {
     match msg {
         Msg::NewTime(time) => match time.parse() {
             Ok(total_time) => (
@@ -44,9 +43,9 @@ fn update(model: Model, msg: Msg) -> (Model, Option<Cmd>) {
                     time_input: time,
                     ..model
                 },
-                None,
+                Cmd::none(),
             ),
-            _ => (model, None),
+            _ => (model, Cmd::none()),
         },
 
         Msg::Stop => (
@@ -55,7 +54,7 @@ fn update(model: Model, msg: Msg) -> (Model, Option<Cmd>) {
                 state: State::Stopped,
                 ..model
             },
-            Some(Cmd::Stop),
+            Cmd::none(),
         ),
 
         Msg::Start => (
@@ -63,16 +62,30 @@ fn update(model: Model, msg: Msg) -> (Model, Option<Cmd>) {
                 state: State::Running,
                 ..model
             },
-            Some(Cmd::Start(model.total_time)),
+            Cmd::none(),
         ),
 
-        Msg::Tick(secs) => (
-            Model {
-                time_elapsed: secs,
-                ..model
-            },
-            None,
-        ),
+        Msg::Tick => {
+            let time_elapsed = model.time_elapsed + 1;
+            if time_elapsed >= model.total_time {
+                update(model, Msg::Stop)
+            } else {
+                (
+                    Model {
+                        time_elapsed,
+                        ..model
+                    },
+                    Cmd::none(),
+                )
+            }
+        }
+    }
+}
+
+fn subscriptions(model: &Model) -> Sub<Msg> {
+    match model.state {
+        State::Running => Sub::interval("tick", std::time::Duration::from_secs(1), || Msg::Tick),
+        State::Stopped => Sub::none(),
     }
 }
 
@@ -110,73 +123,23 @@ fn view(ctx: &egui::Context, model: &Model, tx: &mut Vec<Msg>) {
     });
 }
 
-struct SyncState {
-    stop_flag: Arc<AtomicBool>,
-}
-
-enum Cmd {
-    Start(u64),
-    Stop,
-}
-
-fn sync_state_init() -> SyncState {
-    SyncState {
-        stop_flag: Arc::new(AtomicBool::new(false)),
-    }
-}
-
-fn run_cmd(cmd: Cmd, sync_state: &mut SyncState, tx: chai_tea::ChaiSender<Msg>) {
-    match cmd {
-        Cmd::Start(total_time) => {
-            sync_state.stop_flag.store(false, Ordering::SeqCst);
-            let flag = sync_state.stop_flag.clone();
-
-            std::thread::spawn(move || {
-                let start = std::time::Instant::now();
-                let mut tick = 0;
-
-                loop {
-                    tick += 1;
-
-                    let next = start + std::time::Duration::from_secs(tick);
-                    let now = std::time::Instant::now();
-
-                    let remaining = next.saturating_duration_since(now);
-
-                    if !interruptible_sleep(remaining, &flag) {
-                        break;
-                    }
-
-                    if tx.send(Msg::Tick(tick)).is_err() {
-                        return;
-                    }
-
-                    if tick >= total_time {
-                        if tx.send(Msg::Stop).is_err() {
-                            return;
-                        }
-                        break;
-                    }
-                }
-            });
-        }
-
-        Cmd::Stop => sync_state.stop_flag.store(true, Ordering::SeqCst),
-    }
-}
-
-fn interruptible_sleep(total: std::time::Duration, flag: &AtomicBool) -> bool {
-    let step = std::time::Duration::from_millis(10);
-    let start = std::time::Instant::now();
-    while std::time::Instant::now() - start < total {
-        if flag.load(Ordering::SeqCst) {
-            return false; // interrupted
-        }
-        std::thread::sleep(step);
-    }
-    true // completed
+fn window(model: &Model) -> WindowProps {
+    let title = match model.state {
+        State::Running => format!(
+            "Timer – {}s remaining",
+            model.total_time - model.time_elapsed
+        ),
+        State::Stopped => String::from("Chai Tea Timer"),
+    };
+    WindowProps::new().title(title)
 }
 
 fn main() -> Result<(), eframe::Error> {
-    chai_tea::brew_async("chai_timer", init, sync_state_init, update, view, run_cmd)
+    chai_tea::App::builder("chai_timer")
+        .init(init)
+        .update(update)
+        .view(view)
+        .subscriptions(subscriptions)
+        .window(window)
+        .run()
 }
@@ -0,0 +1,964 @@
+//! The builder every entry point goes through, and the eframe apps it launches.
+
+use std::convert::Infallible;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::Duration;
+
+use eframe::egui;
+
+#[cfg(feature = "persistence")]
+use crate::Persistence;
+use crate::coalesce::{self, CoalesceFn};
+use crate::debugger::Debugger;
+use crate::fixed_step::FixedStepBuilder;
+use crate::pace::Pacer;
+#[cfg(feature = "persistence")]
+use crate::persist::Store;
+use crate::runtime::ErrorFn;
+use crate::runtime::{ExitFn, RunCmdFn, Runtime, SubsFn, Update};
+use crate::window::{Window, WindowEvents};
+use crate::{
+    Budget, ChaiSender, Channel, Cmd, History, Program, Spawner, Sub, WindowEvent, WindowProps,
+};
+#[cfg(feature = "serde")]
+use crate::{Recorder, Replay};
+
+type InitFn<M, Msg, C> = Box<dyn FnOnce() -> (M, Cmd<Msg, C>)>;
+type ViewFn<M, Msg> = Box<dyn Fn(&egui::Context, &M, &mut Vec<Msg>)>;
+type DebuggerFn<M, Msg> = fn(&M) -> Debugger<M, Msg>;
+
+/// Entry point for configuring and running a chai-tea app.
+///
+/// # Example
+/// ```no_run
+/// # use eframe::egui;
+/// # use chai_tea::Cmd;
+/// #[derive(Default)]
+/// struct Model { counter: i32 }
+/// enum Msg { Inc }
+///
+/// fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+///     match msg {
+///         Msg::Inc => (Model { counter: m.counter + 1 }, Cmd::none()),
+///     }
+/// }
+/// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+///
+/// let options = eframe::NativeOptions {
+///     viewport: egui::ViewportBuilder::default().with_inner_size([320.0, 240.0]),
+///     ..Default::default()
+/// };
+///
+/// chai_tea::App::builder("chai_app")
+///     .init(|| (Model::default(), Cmd::none()))
+///     .update(update)
+///     .view(view)
+///     .options(options)
+///     .run();
+/// ```
+pub struct App;
+
+impl App {
+    /// Start configuring an app whose window is titled `title`.
+    ///
+    /// [`init`](AppBuilder::init), an [`update`](AppBuilder::update) or
+    /// [`update_mut`](AppBuilder::update_mut), and [`view`](AppBuilder::view) are required;
+    /// everything else is optional.
+    pub fn builder<M, Msg, C>(title: &str) -> AppBuilder<M, Msg, (), C> {
+        AppBuilder {
+            title: title.to_owned(),
+            options: eframe::NativeOptions::default(),
+            init: None,
+            update: None,
+            view: None,
+            sync_state: Box::new(|| ()),
+            run_cmd: Box::new(|_, _, _| {}),
+            subscriptions: None,
+            spawner: None,
+            channel: None,
+            budget: None,
+            coalesce: None,
+            pacer: Pacer::new(),
+            window_events: None,
+            window: None,
+            on_exit: None,
+            debugger: None,
+            history: None,
+            #[cfg(feature = "serde")]
+            recorder: None,
+            #[cfg(feature = "serde")]
+            replay: None,
+            #[cfg(feature = "persistence")]
+            store: None,
+            on_error: None,
+        }
+    }
+}
+
+impl App {
+    /// Start configuring a simulation titled `title`, stepped every `dt` on its own thread while
+    /// the UI renders the latest snapshot of it.
+    ///
+    /// A fixed-step app is driven by its clock rather than by frames, so it has a builder of its
+    /// own: [`FixedStepBuilder`], where `update` and `view` take the fixed-step shapes and only
+    /// the options that make sense for it are on offer.
+    ///
+    /// # Panics
+    /// If `dt` is zero.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use eframe::egui;
+    /// use chai_tea::{App, StepControl};
+    ///
+    /// #[derive(Clone)]
+    /// struct World { x: f32, speed: f32 }
+    /// enum Msg { SetSpeed(f32) }
+    ///
+    /// fn step(w: World, dt: Duration) -> World {
+    ///     World { x: w.x + w.speed * dt.as_secs_f32(), ..w }
+    /// }
+    ///
+    /// fn update(w: World, msg: Msg) -> World {
+    ///     match msg {
+    ///         Msg::SetSpeed(speed) => World { speed, ..w },
+    ///     }
+    /// }
+    ///
+    /// fn view(ctx: &egui::Context, w: &World, tx: &mut StepControl<Msg>) {
+    ///     egui::CentralPanel::default().show(ctx, |ui| {
+    ///         ui.label(format!("x = {:.2} after {} steps", w.x, tx.steps()));
+    ///         if ui.button("faster").clicked() {
+    ///             tx.push(Msg::SetSpeed(w.speed * 2.0));
+    ///         }
+    ///         if tx.is_paused() {
+    ///             if ui.button("resume").clicked() { tx.resume(); }
+    ///             if ui.button("step").clicked() { tx.step(); }
+    ///         } else if ui.button("pause").clicked() {
+    ///             tx.pause();
+    ///         }
+    ///     });
+    /// }
+    ///
+    /// fn main() -> eframe::Result<()> {
+    ///     App::fixed_step("sim", Duration::from_secs_f64(1.0 / 60.0))
+    ///         .max_catch_up(3)
+    ///         .init(|| World { x: 0.0, speed: 1.0 })
+    ///         .step(step)
+    ///         .update(update)
+    ///         .view(view)
+    ///         .run()
+    /// }
+    /// ```
+    pub fn fixed_step<M, Msg>(title: &str, dt: Duration) -> FixedStepBuilder<M, Msg> {
+        FixedStepBuilder::new(title, dt)
+    }
+
+    /// Start configuring a [`Program`], with everything it defines already set.
+    pub fn program<P: Program>() -> AppBuilder<P::Model, P::Msg, P::Sync, P::Cmd> {
+        let builder = App::builder(P::TITLE)
+            .sync_state(P::sync_state_init)
+            .init(P::init)
+            .update(P::update)
+            .view(P::view)
+            .run_cmd(P::run_cmd)
+            .options(P::options())
+            .subscriptions(P::subscriptions)
+            .on_exit(P::on_exit);
+
+        #[cfg(feature = "persistence")]
+        let builder = AppBuilder {
+            store: Some(Box::new(crate::program::ProgramStore::<P>(
+                std::marker::PhantomData,
+            ))),
+            ..builder
+        };
+
+        builder
+    }
+}
+
+/// Configures an app, see [`App::builder`].
+///
+/// `S` is the `SyncState` type, set by [`sync_state`](AppBuilder::sync_state), and `C` the
+/// app-defined command type handled by [`run_cmd`](AppBuilder::run_cmd).
+pub struct AppBuilder<M, Msg, S = (), C = Infallible> {
+    title: String,
+    options: eframe::NativeOptions,
+    init: Option<InitFn<M, Msg, C>>,
+    update: Option<Update<M, Msg, C>>,
+    view: Option<ViewFn<M, Msg>>,
+    sync_state: Box<dyn FnOnce() -> S>,
+    run_cmd: RunCmdFn<S, Msg, C>,
+    subscriptions: Option<SubsFn<M, Msg>>,
+    spawner: Option<Arc<dyn Spawner>>,
+    channel: Option<Channel<Msg>>,
+    budget: Option<Budget>,
+    coalesce: Option<CoalesceFn<Msg>>,
+    pacer: Pacer<Msg>,
+    window_events: Option<WindowEvents<Msg>>,
+    window: Option<Window<M>>,
+    on_exit: Option<ExitFn<M, S>>,
+    debugger: Option<DebuggerFn<M, Msg>>,
+    history: Option<History<M, Msg>>,
+    #[cfg(feature = "serde")]
+    recorder: Option<Recorder<M, Msg>>,
+    #[cfg(feature = "serde")]
+    replay: Option<Replay<M, Msg>>,
+    #[cfg(feature = "persistence")]
+    store: Option<Box<dyn Store<M>>>,
+    on_error: Option<ErrorFn>,
+}
+
+impl<M, Msg, C> AppBuilder<M, Msg, (), C>
+where
+    M: 'static,
+    Msg: 'static,
+    C: 'static,
+{
+    /// The `SyncState` handed to `run_cmd`, built once when the app starts.
+    ///
+    /// Call it before [`run_cmd`](AppBuilder::run_cmd), whose signature depends on it.
+    pub fn sync_state<S, F>(self, sync_state_init: F) -> AppBuilder<M, Msg, S, C>
+    where
+        F: FnOnce() -> S + 'static,
+    {
+        let run_cmd = self.run_cmd;
+        let on_exit = self.on_exit;
+        AppBuilder {
+            title: self.title,
+            options: self.options,
+            init: self.init,
+            update: self.update,
+            view: self.view,
+            sync_state: Box::new(sync_state_init),
+            run_cmd: Box::new(move |cmd, _, tx| run_cmd(cmd, &mut (), tx)),
+            subscriptions: self.subscriptions,
+            spawner: self.spawner,
+            channel: self.channel,
+            budget: self.budget,
+            coalesce: self.coalesce,
+            pacer: self.pacer,
+            window_events: self.window_events,
+            window: self.window,
+            on_exit: on_exit.map(|on_exit| -> ExitFn<M, S> {
+                Box::new(move |model, _| on_exit(model, &mut ()))
+            }),
+            debugger: self.debugger,
+            history: self.history,
+            #[cfg(feature = "serde")]
+            recorder: self.recorder,
+            #[cfg(feature = "serde")]
+            replay: self.replay,
+            #[cfg(feature = "persistence")]
+            store: self.store,
+            on_error: self.on_error,
+        }
+    }
+}
+
+impl<M, Msg, S, C> AppBuilder<M, Msg, S, C>
+where
+    M: 'static,
+    Msg: 'static,
+{
+    /// The initial model and commands.
+    pub fn init<R, F>(self, init: F) -> Self
+    where
+        R: Into<Cmd<Msg, C>>,
+        F: FnOnce() -> (M, R) + 'static,
+    {
+        Self {
+            init: Some(Box::new(move || {
+                let (model, cmd) = init();
+                (model, cmd.into())
+            })),
+            ..self
+        }
+    }
+
+    /// How messages change the model. May return a `Vec<C>`, an `Option<C>`, or a [`Cmd`].
+    pub fn update<R, F>(self, update: F) -> Self
+    where
+        R: Into<Cmd<Msg, C>>,
+        F: Fn(M, Msg) -> (M, R) + 'static,
+    {
+        Self {
+            update: Some(Update::owned(update)),
+            ..self
+        }
+    }
+
+    /// Like [`update`](AppBuilder::update), changing the model in place rather than returning a
+    /// new one, so large models aren't moved for every message.
+    ///
+    /// May return nothing, a `Vec<C>`, an `Option<C>`, or a [`Cmd`].
+    ///
+    /// ```no_run
+    /// # use eframe::egui;
+    /// use chai_tea::Cmd;
+    ///
+    /// struct Model { samples: Vec<f32> } // no `Default` needed
+    /// enum Msg { Record(f32), Clear }
+    ///
+    /// fn update(m: &mut Model, msg: Msg) {
+    ///     match msg {
+    ///         Msg::Record(sample) => m.samples.push(sample),
+    ///         Msg::Clear => m.samples.clear(),
+    ///     }
+    /// }
+    /// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+    ///
+    /// fn main() -> eframe::Result<()> {
+    ///     chai_tea::App::builder("samples")
+    ///         .init(|| (Model { samples: Vec::with_capacity(1 << 20) }, Cmd::<Msg>::none()))
+    ///         .update_mut(update)
+    ///         .view(view)
+    ///         .run()
+    /// }
+    /// ```
+    pub fn update_mut<R, F>(self, update: F) -> Self
+    where
+        R: Into<Cmd<Msg, C>>,
+        F: Fn(&mut M, Msg) -> R + 'static,
+    {
+        Self {
+            update: Some(Update::in_place(update)),
+            ..self
+        }
+    }
+
+    /// How the model is drawn, pushing messages for `update`.
+    pub fn view<F>(self, view: F) -> Self
+    where
+        F: Fn(&egui::Context, &M, &mut Vec<Msg>) + 'static,
+    {
+        Self {
+            view: Some(Box::new(view)),
+            ..self
+        }
+    }
+
+    /// Carry out app-defined commands. Without one, they are dropped.
+    pub fn run_cmd<F>(self, run_cmd: F) -> Self
+    where
+        F: Fn(C, &mut S, ChaiSender<Msg>) + 'static,
+    {
+        Self {
+            run_cmd: Box::new(run_cmd),
+            ..self
+        }
+    }
+
+    /// Window and backend options for eframe.
+    pub fn options(self, options: eframe::NativeOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Declare [`Sub`]scriptions as a function of the model.
+    ///
+    /// After every update the runtime recomputes `subscriptions(&model)`, starts the sources that
+    /// are new and cancels the ones that disappeared, so recurring work never needs hand-rolled
+    /// stop flags.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use eframe::egui;
+    /// use chai_tea::{Cmd, Sub};
+    ///
+    /// #[derive(Default)]
+    /// struct Model { running: bool, ticks: u64 }
+    /// enum Msg { Toggle, Tick }
+    ///
+    /// fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+    ///     match msg {
+    ///         Msg::Toggle => (Model { running: !m.running, ..m }, Cmd::none()),
+    ///         Msg::Tick => (Model { ticks: m.ticks + 1, ..m }, Cmd::none()),
+    ///     }
+    /// }
+    ///
+    /// fn subscriptions(m: &Model) -> Sub<Msg> {
+    ///     if m.running {
+    ///         Sub::interval("tick", Duration::from_secs(1), || Msg::Tick)
+    ///     } else {
+    ///         Sub::none()
+    ///     }
+    /// }
+    /// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+    ///
+    /// fn main() -> eframe::Result<()> {
+    ///     chai_tea::App::builder("ticker")
+    ///         .init(|| (Model::default(), Cmd::none()))
+    ///         .update(update)
+    ///         .view(view)
+    ///         .subscriptions(subscriptions)
+    ///         .run()
+    /// }
+    /// ```
+    pub fn subscriptions<F>(self, subscriptions: F) -> Self
+    where
+        F: Fn(&M) -> Sub<Msg> + 'static,
+    {
+        Self {
+            subscriptions: Some(Box::new(subscriptions)),
+            ..self
+        }
+    }
+
+    /// Run command work on `spawner`.
+    ///
+    /// Every [`Cmd`] effect and every [`ChaiSender::spawn`] from `run_cmd` runs on it, e.g. a
+    /// [`ThreadPool`](crate::ThreadPool) to cap how many commands run at once.
+    ///
+    /// ```no_run
+    /// # use eframe::egui;
+    /// # use chai_tea::Cmd;
+    /// # fn update(m: i32, msg: i32) -> (i32, Cmd<i32>) { (m, Cmd::none()) }
+    /// # fn view(ctx: &egui::Context, m: &i32, tx: &mut Vec<i32>) { }
+    /// chai_tea::App::builder("chai_app")
+    ///     .init(|| (0, Cmd::none()))
+    ///     .update(update)
+    ///     .view(view)
+    ///     .spawner(chai_tea::ThreadPool::new(4))
+    ///     .run();
+    /// ```
+    pub fn spawner<Sp: Spawner + 'static>(self, spawner: Sp) -> Self {
+        Self {
+            spawner: Some(Arc::new(spawner)),
+            ..self
+        }
+    }
+
+    /// Queue messages sent through [`ChaiSender`]s in `channel`, e.g. a bounded one so a fast
+    /// producer can't outgrow the UI. Unbounded by default.
+    pub fn channel(self, channel: Channel<Msg>) -> Self {
+        Self {
+            channel: Some(channel),
+            ..self
+        }
+    }
+
+    /// Cap how many messages, or how much time, each frame spends in `update`, see [`Budget`].
+    pub fn budget(self, budget: Budget) -> Self {
+        Self {
+            budget: Some(budget),
+            ..self
+        }
+    }
+
+    /// Mark messages whose latest value is all that matters, like progress or ticks.
+    ///
+    /// `key` returns `None` for messages that must all be handled, and a key for the rest: of the
+    /// messages handled in one frame, whether pushed by `view` or sent through a [`ChaiSender`],
+    /// only the newest with each key reaches `update`. See
+    /// [`Simulator::with_coalesce`](crate::Simulator::with_coalesce) for an example.
+    pub fn coalesce<K, F>(self, key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        Self {
+            coalesce: Some(coalesce::keyed(key)),
+            ..self
+        }
+    }
+
+    /// Hold back messages from `view` until no newer one with the same key has come for `quiet`,
+    /// then hand `update` the last of them.
+    ///
+    /// `key` returns `None` for messages to pass straight through. Typing in a search box, say,
+    /// then reaches `update` once the user pauses, not on every keystroke. The runtime repaints
+    /// when the settled message is due, so it arrives without waiting for more input.
+    ///
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use eframe::egui;
+    /// # use chai_tea::Cmd;
+    /// enum Msg { Query(String), Clear }
+    ///
+    /// # fn update(m: String, msg: Msg) -> (String, Cmd<Msg>) { (m, Cmd::none()) }
+    /// # fn view(ctx: &egui::Context, m: &String, tx: &mut Vec<Msg>) {}
+    /// fn main() -> eframe::Result<()> {
+    ///     chai_tea::App::builder("search")
+    ///         .init(|| (String::new(), Cmd::none()))
+    ///         .update(update)
+    ///         .view(view)
+    ///         .debounce(Duration::from_millis(300), |msg| match msg {
+    ///             Msg::Query(_) => Some("query"),
+    ///             Msg::Clear => None,
+    ///         })
+    ///         .run()
+    /// }
+    /// ```
+    pub fn debounce<K, F>(mut self, quiet: Duration, key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        self.pacer.debounce(quiet, key);
+        self
+    }
+
+    /// Let through at most one message from `view` per `interval` for each key `key` returns.
+    ///
+    /// The first goes straight to `update`; the newest of any that follow too soon is delivered
+    /// once the interval is up, with a repaint scheduled for it. For at most `n` per second, use
+    /// `Duration::from_secs(1) / n`. Like [`debounce`](AppBuilder::debounce), `None` means the
+    /// message isn't throttled.
+    pub fn throttle<K, F>(mut self, interval: Duration, key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        self.pacer.throttle(interval, key);
+        self
+    }
+
+    /// Deliver what happens to the window as messages: `to_msg` maps each [`WindowEvent`] to a
+    /// message, or to `None` to ignore it.
+    ///
+    /// They are handled in the frame they happen, after `view`'s messages, so an `update` that
+    /// returns [`Cmd::cancel_close`] on [`WindowEvent::CloseRequested`] keeps the window open.
+    /// Cleanup for when it does close belongs in [`on_exit`](AppBuilder::on_exit).
+    ///
+    /// ```no_run
+    /// # use eframe::egui;
+    /// use chai_tea::{Cmd, WindowEvent};
+    ///
+    /// struct Model { unsaved: bool, confirm_quit: bool }
+    /// enum Msg { CloseRequested, Focused(bool) }
+    ///
+    /// fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+    ///     match msg {
+    ///         Msg::CloseRequested if m.unsaved => (Model { confirm_quit: true, ..m }, Cmd::cancel_close()),
+    ///         Msg::CloseRequested | Msg::Focused(_) => (m, Cmd::none()),
+    ///     }
+    /// }
+    /// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) {}
+    ///
+    /// fn main() -> eframe::Result<()> {
+    ///     chai_tea::App::builder("editor")
+    ///         .init(|| (Model { unsaved: true, confirm_quit: false }, Cmd::none()))
+    ///         .update(update)
+    ///         .view(view)
+    ///         .window_events(|event| match event {
+    ///             WindowEvent::CloseRequested => Some(Msg::CloseRequested),
+    ///             WindowEvent::FocusGained => Some(Msg::Focused(true)),
+    ///             WindowEvent::FocusLost => Some(Msg::Focused(false)),
+    ///             _ => None,
+    ///         })
+    ///         .run()
+    /// }
+    /// ```
+    pub fn window_events<F>(self, to_msg: F) -> Self
+    where
+        F: Fn(WindowEvent) -> Option<Msg> + 'static,
+    {
+        Self {
+            window_events: Some(WindowEvents::new(to_msg)),
+            ..self
+        }
+    }
+
+    /// Derive the window's title, size limits and decorations from the model.
+    ///
+    /// `window` runs after every frame's `update`; only the properties that changed since the
+    /// last frame are sent to the window, as viewport commands. The title given to
+    /// [`App::builder`] is then only the one shown until the first frame, and the name eframe
+    /// stores the app's settings under.
+    ///
+    /// ```no_run
+    /// # use eframe::egui;
+    /// use chai_tea::{Cmd, WindowProps};
+    ///
+    /// struct Model { remaining: u32, kiosk: bool }
+    /// # enum Msg {}
+    /// # fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) { (m, Cmd::none()) }
+    /// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) {}
+    ///
+    /// fn window(m: &Model) -> WindowProps {
+    ///     WindowProps::new()
+    ///         .title(format!("Timer – {}s remaining", m.remaining))
+    ///         .min_size([240.0, 120.0])
+    ///         .decorations(!m.kiosk)
+    /// }
+    ///
+    /// fn main() -> eframe::Result<()> {
+    ///     chai_tea::App::builder("timer")
+    ///         .init(|| (Model { remaining: 10, kiosk: false }, Cmd::none()))
+    ///         .update(update)
+    ///         .view(view)
+    ///         .window(window)
+    ///         .run()
+    /// }
+    /// ```
+    pub fn window<F>(self, window: F) -> Self
+    where
+        F: Fn(&M) -> WindowProps + 'static,
+    {
+        Self {
+            window: Some(Window::new(window)),
+            ..self
+        }
+    }
+
+    /// Dock the time-travel debugger panel on the right.
+    ///
+    /// Every message is recorded with a snapshot of the model `update` returned for it. Drag the
+    /// timeline or click an entry to re-render `view` against that snapshot; live processing
+    /// pauses while you look, and incoming messages queue up until you hit resume. Nothing is
+    /// re-run while scrubbing, and clicks in a past view are ignored. Messages from
+    /// [`window_events`](AppBuilder::window_events) are the exception and are handled right
+    /// away, so closing the window still goes through `update`.
+    ///
+    /// ```no_run
+    /// # use eframe::egui;
+    /// # use chai_tea::Cmd;
+    /// #[derive(Clone, Default)]
+    /// struct Model { counter: i32 }
+    /// #[derive(Debug)]
+    /// enum Msg { Inc }
+    ///
+    /// # fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) { (m, Cmd::none()) }
+    /// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+    /// chai_tea::App::builder("chai_app")
+    ///     .init(|| (Model::default(), Cmd::none()))
+    ///     .update(update)
+    ///     .view(view)
+    ///     .debugger()
+    ///     .run();
+    /// ```
+    pub fn debugger(self) -> Self
+    where
+        M: Clone,
+        Msg: std::fmt::Debug,
+    {
+        Self {
+            debugger: Some(Debugger::new),
+            ..self
+        }
+    }
+
+    /// Called once when the window closes, with the final model and the `SyncState`, e.g. to
+    /// join worker threads or flush files.
+    pub fn on_exit<F>(self, on_exit: F) -> Self
+    where
+        F: FnOnce(&M, &mut S) + 'static,
+    {
+        Self {
+            on_exit: Some(Box::new(on_exit)),
+            ..self
+        }
+    }
+
+    /// Handle undo and redo, see [`History`].
+    pub fn history(self, history: History<M, Msg>) -> Self {
+        Self {
+            history: Some(history),
+            ..self
+        }
+    }
+
+    /// Log every processed message, see [`Recorder`].
+    #[cfg(feature = "serde")]
+    pub fn recorder(self, recorder: Recorder<M, Msg>) -> Self {
+        Self {
+            recorder: Some(recorder),
+            ..self
+        }
+    }
+
+    /// Play a recorded session back into the window, at the pace it was recorded.
+    ///
+    /// Input from `view` is ignored, and neither commands nor subscriptions run, so the window
+    /// shows exactly the recorded session. A bar at the bottom shows progress, and the first
+    /// [`Divergence`](crate::Divergence) if the replay checks model hashes.
+    ///
+    /// ```no_run
+    /// # use eframe::egui;
+    /// # #[derive(Default, Hash)]
+    /// # struct Model { counter: i32 }
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # enum Msg { Inc, Dec }
+    /// # fn update(m: Model, msg: Msg) -> (Model, Vec<()>) { (m, vec![]) }
+    /// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+    /// let replay = chai_tea::Replay::open("session.jsonl")?.with_model_hash();
+    ///
+    /// chai_tea::App::builder("chai_app")
+    ///     .init(|| (Model::default(), vec![]))
+    ///     .update(update)
+    ///     .view(view)
+    ///     .replay(replay)
+    ///     .run();
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn replay(self, replay: Replay<M, Msg>) -> Self {
+        Self {
+            replay: Some(replay),
+            ..self
+        }
+    }
+
+    /// Restore the model at startup and save it on exit, see [`Persistence`].
+    #[cfg(feature = "persistence")]
+    pub fn persistence<P>(self, persistence: Persistence<M, P>) -> Self
+    where
+        P: serde::de::DeserializeOwned + 'static,
+    {
+        Self {
+            store: Some(Box::new(persistence)),
+            ..self
+        }
+    }
+
+    /// Be told about failures the app recovers from on its own, like a save it can't restore or
+    /// a log the [`Recorder`](crate::Recorder) can't write. See [`Error`](crate::Error) for what
+    /// gets reported; without a hook it's all ignored.
+    ///
+    /// ```no_run
+    /// # use eframe::egui;
+    /// # #[derive(Default)]
+    /// # struct Model { counter: i32 }
+    /// # enum Msg { Inc }
+    /// # fn update(m: Model, msg: Msg) -> (Model, chai_tea::Cmd<Msg>) { (m, chai_tea::Cmd::none()) }
+    /// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+    /// chai_tea::App::builder("chai_app")
+    ///     .init(|| (Model::default(), chai_tea::Cmd::none()))
+    ///     .update(update)
+    ///     .view(view)
+    ///     .on_error(|err| eprintln!("chai_app: {err}"))
+    ///     .run();
+    /// ```
+    pub fn on_error<F>(self, on_error: F) -> Self
+    where
+        F: Fn(crate::Error) + 'static,
+    {
+        Self {
+            on_error: Some(Box::new(on_error)),
+            ..self
+        }
+    }
+}
+
+impl<M, Msg, S, C> AppBuilder<M, Msg, S, C>
+where
+    M: 'static,
+    S: 'static,
+    C: 'static,
+    Msg: 'static,
+{
+    /// Open the window and run the app until it is closed.
+    ///
+    /// Everything the app needs lives in the app itself, so once the window closes another app,
+    /// or the same one, can be run in the same process.
+    ///
+    /// # Panics
+    /// If [`init`](AppBuilder::init), [`update`](AppBuilder::update) or
+    /// [`view`](AppBuilder::view) was never set.
+    pub fn run(self) -> eframe::Result<()>
+    where
+        Msg: Send,
+    {
+        self.launch(|runtime, view| ChaiTeaAppAsync { runtime, view })
+    }
+
+    /// Like [`run`](AppBuilder::run), for apps whose messages can't cross threads.
+    ///
+    /// Every command is carried out on the UI thread: window commands like [`Cmd::set_title`],
+    /// delayed messages from [`Cmd::after`], and app-defined commands through `run_cmd`.
+    /// [`Cmd::perform`] and futures need `Send` messages, so they can't be built for these apps;
+    /// one that slips through [`Cmd::map`] from a `Send` child is dropped and reported as
+    /// [`Error::CommandDropped`](crate::Error::CommandDropped), and panics in debug builds.
+    ///
+    /// ```no_run
+    /// # use std::rc::Rc;
+    /// # use eframe::egui;
+    /// use chai_tea::Cmd;
+    ///
+    /// struct Model { score: u32 }
+    /// enum Msg { Scored(Rc<str>) } // not `Send`
+    ///
+    /// fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+    ///     match msg {
+    ///         Msg::Scored(by) => {
+    ///             let score = m.score + 1;
+    ///             (Model { score }, Cmd::set_title(format!("{score} – last by {by}")))
+    ///         }
+    ///     }
+    /// }
+    /// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) {}
+    ///
+    /// fn main() -> eframe::Result<()> {
+    ///     chai_tea::App::builder("scores")
+    ///         .init(|| (Model { score: 0 }, Cmd::none()))
+    ///         .update(update)
+    ///         .view(view)
+    ///         .run_local()
+    /// }
+    /// ```
+    pub fn run_local(self) -> eframe::Result<()> {
+        self.launch(|runtime, view| ChaiTeaApp { runtime, view })
+    }
+
+    // the creation context is only read to restore persisted state
+    #[cfg_attr(not(feature = "persistence"), allow(unused_variables))]
+    fn launch<A, Fapp>(self, app: Fapp) -> eframe::Result<()>
+    where
+        A: eframe::App + 'static,
+        Fapp: FnOnce(Runtime<M, S, C, Msg>, ViewFn<M, Msg>) -> A + 'static,
+    {
+        let Self {
+            title,
+            options,
+            init,
+            update,
+            view,
+            sync_state,
+            run_cmd,
+            subscriptions,
+            spawner,
+            channel,
+            budget,
+            coalesce,
+            pacer,
+            window_events,
+            window,
+            on_exit,
+            debugger,
+            history,
+            #[cfg(feature = "serde")]
+            recorder,
+            #[cfg(feature = "serde")]
+            replay,
+            #[cfg(feature = "persistence")]
+            store,
+            on_error,
+        } = self;
+        let init = init.expect("chai_tea::App::builder needs an .init()");
+        let update = update.expect("chai_tea::App::builder needs an .update()");
+        let view = view.expect("chai_tea::App::builder needs a .view()");
+
+        eframe::run_native(
+            &title,
+            options,
+            Box::new(move |cc| {
+                let mut runtime = Runtime::new(init(), sync_state(), update, run_cmd);
+
+                if let Some(subscriptions) = subscriptions {
+                    runtime = runtime.with_subscriptions(subscriptions);
+                }
+                if let Some(spawner) = spawner {
+                    runtime = runtime.with_spawner(spawner);
+                }
+                if let Some(channel) = channel {
+                    runtime = runtime.with_channel(channel);
+                }
+                if let Some(budget) = budget {
+                    runtime = runtime.with_budget(budget);
+                }
+                if let Some(coalesce) = coalesce {
+                    runtime = runtime.with_coalesce(coalesce);
+                }
+                runtime = runtime.with_pacer(pacer);
+                if let Some(window_events) = window_events {
+                    runtime = runtime.with_window_events(window_events);
+                }
+                if let Some(window) = window {
+                    runtime = runtime.with_window(window);
+                }
+                if let Some(on_exit) = on_exit {
+                    runtime = runtime.with_on_exit(on_exit);
+                }
+                if let Some(on_error) = on_error {
+                    runtime = runtime.with_on_error(on_error);
+                }
+                #[cfg(feature = "persistence")]
+                if let Some(store) = store {
+                    runtime = runtime.with_store(store, cc.storage);
+                }
+                if let Some(history) = history {
+                    runtime = runtime.with_history(history);
+                }
+                #[cfg(feature = "serde")]
+                if let Some(recorder) = recorder {
+                    runtime = runtime.with_recorder(recorder);
+                }
+                #[cfg(feature = "serde")]
+                if let Some(replay) = replay {
+                    runtime = runtime.with_replay(replay);
+                }
+                if let Some(debugger) = debugger {
+                    runtime = runtime.with_debugger(debugger);
+                }
+
+                Ok(Box::new(app(runtime, view)))
+            }),
+        )
+    }
+}
+
+/// An app that never runs commands, so its messages needn't be `Send`.
+struct ChaiTeaApp<M, S, C, Msg> {
+    runtime: Runtime<M, S, C, Msg>,
+    view: ViewFn<M, Msg>,
+}
+
+impl<M, S, C, Msg> eframe::App for ChaiTeaApp<M, S, C, Msg>
+where
+    M: 'static,
+    S: 'static,
+    C: 'static,
+    Msg: 'static,
+{
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let cmds = self.runtime.start(ctx);
+
+        self.runtime.view(ctx, &self.view);
+        let cmds = self.runtime.update(cmds);
+        self.runtime.run_local_cmds(cmds);
+    }
+
+    #[cfg(feature = "persistence")]
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.runtime.save(storage);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.runtime.exit();
+    }
+}
+
+struct ChaiTeaAppAsync<M, S, C, Msg> {
+    runtime: Runtime<M, S, C, Msg>,
+    view: ViewFn<M, Msg>,
+}
+
+impl<M, S, C, Msg> eframe::App for ChaiTeaAppAsync<M, S, C, Msg>
+where
+    M: 'static,
+    S: 'static,
+    C: 'static,
+    Msg: Send + 'static,
+{
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let cmds = self.runtime.start(ctx);
+
+        self.runtime.view(ctx, &self.view);
+        let cmds = self.runtime.update(cmds);
+
+        //run async cmds
+        self.runtime.run_cmds(cmds);
+    }
+
+    #[cfg(feature = "persistence")]
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.runtime.save(storage);
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.runtime.exit();
+    }
+}
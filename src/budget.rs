@@ -0,0 +1,111 @@
+//! A cap on how much of a frame `update` may spend on queued messages.
+
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+/// How many messages a frame hands to `update`, or how long it may take, before the rest wait
+/// for the next frame.
+///
+/// Without a budget every queued message is handled before the UI renders again, so a flood
+/// from background work stalls the window until it's through. With one, the frame stops once
+/// the budget is spent and requests an immediate repaint to carry on; [`queue_depth`] tells
+/// `view` how far behind it is. At least one message is handled per frame.
+///
+/// Held-back messages keep their place: the next frame handles them before its own `view`
+/// messages, so a deferred click never runs after a newer one. Messages from
+/// [`ChaiSender`](crate::ChaiSender)s are only taken from the channel as the budget allows, so a
+/// flood of them can't hold back input. With the [debugger](crate::AppBuilder::debugger) docked
+/// the budget applies to whatever it lets through; while it's paused, messages wait there.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use chai_tea::{Budget, Simulator};
+///
+/// let mut sim = Simulator::new(|| (0, vec![]), || (), |n: i32, m: i32| (n + m, vec![]), |(), _, _| {})
+///     .with_budget(Budget::new().max_messages(2).max_time(Duration::from_millis(4)));
+/// let tx = sim.sender();
+/// for _ in 0..5 {
+///     tx.send(1).unwrap();
+/// }
+///
+/// sim.step();
+/// assert_eq!((*sim.model(), sim.queue_depth()), (2, 3));
+/// sim.step();
+/// sim.step();
+/// assert_eq!((*sim.model(), sim.queue_depth()), (5, 0));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    messages: Option<usize>,
+    time: Option<Duration>,
+}
+
+impl Budget {
+    /// No limit, the same as having no budget. Narrow it with the methods below.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle at most `messages` per frame.
+    pub fn max_messages(self, messages: usize) -> Self {
+        Self {
+            messages: Some(messages),
+            ..self
+        }
+    }
+
+    /// Stop handling messages once a frame has spent `time` in `update`.
+    pub fn max_time(self, time: Duration) -> Self {
+        Self {
+            time: Some(time),
+            ..self
+        }
+    }
+
+    /// Start counting down for one frame.
+    pub(crate) fn start(self) -> Spending {
+        Spending {
+            budget: self,
+            started: Instant::now(),
+            handled: 0,
+        }
+    }
+}
+
+/// A [`Budget`] being spent over one frame.
+pub(crate) struct Spending {
+    budget: Budget,
+    started: Instant,
+    handled: usize,
+}
+
+impl Spending {
+    /// Count one handled message, returning whether the frame may handle another.
+    pub(crate) fn handled(&mut self) -> bool {
+        self.handled += 1;
+        let under_count = self.budget.messages.is_none_or(|max| self.handled < max);
+        let under_time = self
+            .budget
+            .time
+            .is_none_or(|max| self.started.elapsed() < max);
+        under_count && under_time
+    }
+}
+
+fn depth_id() -> egui::Id {
+    egui::Id::new("chai_tea::queue_depth")
+}
+
+/// How many messages were still waiting for `update` at the end of the last frame.
+///
+/// Non-zero means a [`Budget`] is holding messages back, or they're arriving faster than
+/// frames are drawn; either way the app is catching up, which `view` may want to show.
+pub fn queue_depth(ctx: &egui::Context) -> usize {
+    ctx.data(|data| data.get_temp(depth_id())).unwrap_or(0)
+}
+
+pub(crate) fn set_queue_depth(ctx: &egui::Context, depth: usize) {
+    ctx.data_mut(|data| data.insert_temp(depth_id(), depth));
+}
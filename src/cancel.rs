@@ -0,0 +1,125 @@
+//! Cooperative cancellation shared between the runtime and the work it starts.
+
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+/// Hash any user-supplied id into the key the runtime tracks tasks and sources by.
+pub(crate) fn key_of(id: impl Hash) -> u64 {
+    let mut hasher = std::hash::DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Default)]
+struct State {
+    cancelled: bool,
+    wakers: Vec<Waker>,
+}
+
+/// A flag shared with a running task, raised when the runtime wants it to stop.
+///
+/// Threads can poll [`is_cancelled`](CancelToken::is_cancelled) or sleep on the token;
+/// futures can await [`cancelled`](CancelToken::cancelled). Cloning gives another handle to
+/// the same flag.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the task to stop, waking it if it's sleeping or awaiting this token.
+    pub fn cancel(&self) {
+        let (state, wake) = &*self.inner;
+        let wakers = {
+            let mut state = state.lock().unwrap();
+            state.cancelled = true;
+            std::mem::take(&mut state.wakers)
+        };
+        wake.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.0.lock().unwrap().cancelled
+    }
+
+    /// Sleep for `duration`, returning early if cancelled.
+    ///
+    /// Returns `true` if the full duration elapsed, `false` if cancelled.
+    pub fn sleep(&self, duration: Duration) -> bool {
+        self.sleep_until(Instant::now() + duration)
+    }
+
+    /// Sleep until `deadline`, returning early if cancelled.
+    ///
+    /// Returns `true` if the deadline was reached, `false` if cancelled.
+    pub fn sleep_until(&self, deadline: Instant) -> bool {
+        let (state, wake) = &*self.inner;
+        let mut state = state.lock().unwrap();
+        loop {
+            if state.cancelled {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            state = wake.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+
+    /// A future that resolves once the token is cancelled.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            token: self.clone(),
+        }
+    }
+
+    /// Whether only the runtime still holds this token, i.e. the task it was handed to is done.
+    pub(crate) fn is_orphaned(&self) -> bool {
+        Arc::strong_count(&self.inner) == 1
+    }
+}
+
+/// Future returned by [`CancelToken::cancelled`].
+pub struct Cancelled {
+    token: CancelToken,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.token.inner.0.lock().unwrap();
+        if state.cancelled {
+            return Poll::Ready(());
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+/// Resolve to `future`'s output, or to `None` as soon as `token` is cancelled.
+#[cfg(feature = "tokio")]
+pub(crate) async fn until_cancelled<F: Future>(token: CancelToken, future: F) -> Option<F::Output> {
+    let mut future = std::pin::pin!(future);
+    let mut cancelled = token.cancelled();
+
+    std::future::poll_fn(move |cx| {
+        if Pin::new(&mut cancelled).poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        future.as_mut().poll(cx).map(Some)
+    })
+    .await
+}
@@ -0,0 +1,398 @@
+//! The queue that messages from `run_cmd`, subscriptions and background work wait in for `update`.
+
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::sync::mpsc::{self, Receiver, SendError, Sender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::cancel::key_of;
+
+/// How many messages a [`ChaiSender`](crate::ChaiSender) may queue before the next frame drains
+/// them, and what happens past that.
+///
+/// The default is unbounded, which never loses a message but lets a producer that outpaces the
+/// UI grow memory for as long as it keeps going. Bound it for high-rate sources like telemetry:
+///
+/// ```no_run
+/// # use eframe::egui;
+/// use chai_tea::{App, Channel, Cmd, Overflow};
+///
+/// enum Msg { Reading { sensor: u32, value: f32 } }
+///
+/// # fn init() -> (Vec<f32>, Cmd<Msg>) { (vec![0.0; 8], Cmd::none()) }
+/// # fn update(m: Vec<f32>, msg: Msg) -> (Vec<f32>, Cmd<Msg>) { (m, Cmd::none()) }
+/// # fn view(ctx: &egui::Context, m: &Vec<f32>, tx: &mut Vec<Msg>) {}
+/// fn main() -> eframe::Result<()> {
+///     // only the newest reading of each sensor is worth keeping
+///     let latest = Overflow::coalesce(|Msg::Reading { sensor, .. }| *sensor);
+///
+///     App::builder("telemetry")
+///         .init(init)
+///         .update(update)
+///         .view(view)
+///         .channel(Channel::bounded(64, latest))
+///         .run()
+/// }
+/// ```
+pub struct Channel<Msg> {
+    capacity: Option<usize>,
+    overflow: Overflow<Msg>,
+}
+
+impl<Msg> Channel<Msg> {
+    /// Queue every message, however many pile up. This is the default.
+    pub fn unbounded() -> Self {
+        Self {
+            capacity: None,
+            overflow: Overflow::block(),
+        }
+    }
+
+    /// Queue at most `capacity` messages, handling the rest as `overflow` says.
+    ///
+    /// # Panics
+    /// If `capacity` is zero.
+    pub fn bounded(capacity: usize, overflow: Overflow<Msg>) -> Self {
+        assert!(capacity > 0, "a bounded channel needs room for a message");
+        Self {
+            capacity: Some(capacity),
+            overflow,
+        }
+    }
+
+    pub(crate) fn open(self) -> (Transport<Msg>, Inbox<Msg>) {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                msgs: VecDeque::new(),
+                closed: false,
+            }),
+            room: Condvar::new(),
+            capacity: self.capacity.unwrap_or(usize::MAX),
+            policy: self.overflow.policy,
+        });
+        let (bypass_tx, bypass_rx) = mpsc::channel();
+        let transport = Transport::Queue {
+            shared: Arc::clone(&shared),
+            bypass: bypass_tx,
+        };
+        (
+            transport,
+            Inbox {
+                shared,
+                bypass: bypass_rx,
+            },
+        )
+    }
+}
+
+impl<Msg> Default for Channel<Msg> {
+    fn default() -> Self {
+        Self::unbounded()
+    }
+}
+
+/// What a bounded [`Channel`] does with a message that arrives while it's full.
+pub struct Overflow<Msg> {
+    policy: Policy<Msg>,
+}
+
+type KeyFn<Msg> = Box<dyn Fn(&Msg) -> u64 + Send + Sync>;
+
+enum Policy<Msg> {
+    Block,
+    DropNewest,
+    DropOldest,
+    Coalesce(KeyFn<Msg>),
+}
+
+impl<Msg> Overflow<Msg> {
+    /// Make `send` wait until the next frame makes room, and `try_send` fail with
+    /// [`TrySendError::Full`].
+    ///
+    /// Nothing is lost, and a producer is slowed to the UI's pace. Never `send` from `run_cmd`
+    /// itself with this policy: it runs on the UI thread, which is the one that would make room.
+    pub fn block() -> Self {
+        Self {
+            policy: Policy::Block,
+        }
+    }
+
+    /// Discard the message being sent, keeping what's already queued.
+    pub fn drop_newest() -> Self {
+        Self {
+            policy: Policy::DropNewest,
+        }
+    }
+
+    /// Discard the oldest queued message to make room for the new one.
+    pub fn drop_oldest() -> Self {
+        Self {
+            policy: Policy::DropOldest,
+        }
+    }
+
+    /// Replace the queued message with the same `key` as the new one, keeping its place in line.
+    /// If no queued message shares the key, the oldest is discarded instead.
+    pub fn coalesce<K, F>(key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&Msg) -> K + Send + Sync + 'static,
+    {
+        Self {
+            policy: Policy::Coalesce(Box::new(move |msg| key_of(key(msg)))),
+        }
+    }
+}
+
+/// What [`ChaiSender::try_send`](crate::ChaiSender::try_send) did with a message.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Delivery<T> {
+    /// There was room; the message is queued.
+    Queued,
+    /// The channel was full and [`Overflow::drop_newest`] turned the message away. Here it is back.
+    DroppedNewest(T),
+    /// The channel was full; the message is queued and this, the oldest, was discarded for it.
+    DroppedOldest(T),
+    /// The channel was full; the message took the place of this queued one with the same key.
+    Coalesced(T),
+}
+
+struct Queue<T> {
+    msgs: VecDeque<T>,
+    /// Set once the app is gone, so senders stop queueing and blocked ones wake up.
+    closed: bool,
+}
+
+pub(crate) struct Shared<T> {
+    queue: Mutex<Queue<T>>,
+    room: Condvar,
+    capacity: usize,
+    policy: Policy<T>,
+}
+
+/// The sending half of a [`Channel`], inside every [`ChaiSender`](crate::ChaiSender).
+pub(crate) enum Transport<T> {
+    /// A plain `mpsc` sender, from [`ChaiSender::new`](crate::ChaiSender::new).
+    Mpsc(Sender<T>),
+    Queue {
+        shared: Arc<Shared<T>>,
+        /// Straight into the inbox, past the bound and policy; what the deprecated
+        /// `ChaiSender::mpsc` hands out.
+        bypass: Sender<T>,
+    },
+}
+
+impl<T> Transport<T> {
+    /// The plain `mpsc` sender behind this transport.
+    pub(crate) fn mpsc(&self) -> &Sender<T> {
+        match self {
+            Transport::Mpsc(tx) | Transport::Queue { bypass: tx, .. } => tx,
+        }
+    }
+
+    /// Queue `msg`, waiting for room under [`Overflow::block`] if `wait` is set.
+    pub(crate) fn send(&self, msg: T, wait: bool) -> Result<Delivery<T>, TrySendError<T>> {
+        let shared = match self {
+            Transport::Mpsc(tx) => {
+                return tx
+                    .send(msg)
+                    .map(|()| Delivery::Queued)
+                    .map_err(|SendError(msg)| TrySendError::Disconnected(msg));
+            }
+            Transport::Queue { shared, .. } => shared,
+        };
+
+        let Ok(mut queue) = shared.queue.lock() else {
+            return Err(TrySendError::Disconnected(msg));
+        };
+        if wait && matches!(shared.policy, Policy::Block) {
+            while !queue.closed && queue.msgs.len() >= shared.capacity {
+                queue = match shared.room.wait(queue) {
+                    Ok(queue) => queue,
+                    Err(_) => return Err(TrySendError::Disconnected(msg)),
+                };
+            }
+        }
+        if queue.closed {
+            return Err(TrySendError::Disconnected(msg));
+        }
+        if queue.msgs.len() < shared.capacity {
+            queue.msgs.push_back(msg);
+            return Ok(Delivery::Queued);
+        }
+
+        match &shared.policy {
+            Policy::Block => Err(TrySendError::Full(msg)),
+            Policy::DropNewest => Ok(Delivery::DroppedNewest(msg)),
+            Policy::DropOldest => Ok(Delivery::DroppedOldest(queue.evict(msg))),
+            Policy::Coalesce(key) => {
+                let new = key(&msg);
+                match queue.msgs.iter_mut().find(|queued| key(queued) == new) {
+                    Some(queued) => Ok(Delivery::Coalesced(std::mem::replace(queued, msg))),
+                    None => Ok(Delivery::DroppedOldest(queue.evict(msg))),
+                }
+            }
+        }
+    }
+}
+
+impl<T> Queue<T> {
+    /// Drop the front message for `msg`, returning it. Only called on a full, so non-empty, queue.
+    fn evict(&mut self, msg: T) -> T {
+        let oldest = self.msgs.pop_front().expect("a full queue has a front");
+        self.msgs.push_back(msg);
+        oldest
+    }
+}
+
+impl<T> Clone for Transport<T> {
+    fn clone(&self) -> Self {
+        match self {
+            Transport::Mpsc(tx) => Transport::Mpsc(tx.clone()),
+            Transport::Queue { shared, bypass } => Transport::Queue {
+                shared: Arc::clone(shared),
+                bypass: bypass.clone(),
+            },
+        }
+    }
+}
+
+/// The receiving half of a [`Channel`], drained by the runtime each frame.
+pub(crate) struct Inbox<T> {
+    shared: Arc<Shared<T>>,
+    bypass: Receiver<T>,
+}
+
+impl<T> Inbox<T> {
+    /// Lock the queue, first moving in whatever came through the bypass.
+    fn queue(&self) -> Option<std::sync::MutexGuard<'_, Queue<T>>> {
+        let mut queue = self.shared.queue.lock().ok()?;
+        queue.msgs.extend(self.bypass.try_iter());
+        Some(queue)
+    }
+
+    /// Take everything queued so far, making room for blocked senders.
+    pub(crate) fn drain(&self) -> Vec<T> {
+        let msgs = match self.queue() {
+            Some(mut queue) => queue.msgs.drain(..).collect(),
+            None => Vec::new(),
+        };
+        self.shared.room.notify_all();
+        msgs
+    }
+
+    /// Take the oldest queued message, if any.
+    pub(crate) fn pop(&self) -> Option<T> {
+        let msg = self.queue()?.msgs.pop_front();
+        if msg.is_some() {
+            self.shared.room.notify_one();
+        }
+        msg
+    }
+
+    /// How many messages are waiting.
+    pub(crate) fn len(&self) -> usize {
+        self.queue().map_or(0, |queue| queue.msgs.len())
+    }
+}
+
+impl<T> Drop for Inbox<T> {
+    fn drop(&mut self) {
+        if let Ok(mut queue) = self.shared.queue.lock() {
+            queue.closed = true;
+            queue.msgs.clear();
+        }
+        self.shared.room.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// A message with a key, and a number to tell it apart.
+    type Keyed = (char, u32);
+
+    /// Fill a channel of capacity 2, try to send one more, and see what's left queued.
+    fn overflow(
+        policy: Overflow<Keyed>,
+    ) -> (Result<Delivery<Keyed>, TrySendError<Keyed>>, Vec<Keyed>) {
+        let (tx, inbox) = Channel::bounded(2, policy).open();
+        tx.send(('a', 1), false).unwrap();
+        tx.send(('b', 2), false).unwrap();
+        let delivery = tx.send(('a', 3), false);
+        (delivery, inbox.drain())
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_queue() {
+        let (delivery, kept) = overflow(Overflow::drop_newest());
+        assert_eq!(delivery, Ok(Delivery::DroppedNewest(('a', 3))));
+        assert_eq!(kept, [('a', 1), ('b', 2)]);
+    }
+
+    #[test]
+    fn drop_oldest_makes_room_at_the_back() {
+        let (delivery, kept) = overflow(Overflow::drop_oldest());
+        assert_eq!(delivery, Ok(Delivery::DroppedOldest(('a', 1))));
+        assert_eq!(kept, [('b', 2), ('a', 3)]);
+    }
+
+    #[test]
+    fn coalesce_replaces_the_same_key_in_place() {
+        let (delivery, kept) = overflow(Overflow::coalesce(|(key, _): &Keyed| *key));
+        assert_eq!(delivery, Ok(Delivery::Coalesced(('a', 1))));
+        assert_eq!(kept, [('a', 3), ('b', 2)]);
+    }
+
+    #[test]
+    fn coalesce_without_a_match_drops_the_oldest() {
+        let (delivery, kept) = overflow(Overflow::coalesce(|(_, n): &Keyed| *n));
+        assert_eq!(delivery, Ok(Delivery::DroppedOldest(('a', 1))));
+        assert_eq!(kept, [('b', 2), ('a', 3)]);
+    }
+
+    #[test]
+    fn block_refuses_without_waiting() {
+        let (delivery, kept) = overflow(Overflow::block());
+        assert_eq!(delivery, Err(TrySendError::Full(('a', 3))));
+        assert_eq!(kept, [('a', 1), ('b', 2)]);
+    }
+
+    #[test]
+    fn block_waits_for_room_and_loses_nothing() {
+        let (tx, inbox) = Channel::bounded(2, Overflow::block()).open();
+        tx.send(1, false).unwrap();
+        tx.send(2, false).unwrap();
+        let sender = std::thread::spawn(move || tx.send(3, true));
+
+        // give the sender time to block on the full queue
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(inbox.drain(), [1, 2]);
+        assert_eq!(sender.join().unwrap(), Ok(Delivery::Queued));
+        assert_eq!(inbox.drain(), [3]);
+    }
+
+    #[test]
+    fn dropping_the_inbox_wakes_blocked_senders() {
+        let (tx, inbox) = Channel::bounded(1, Overflow::block()).open();
+        tx.send(1, false).unwrap();
+        let sender = std::thread::spawn(move || tx.send(2, true));
+
+        std::thread::sleep(Duration::from_millis(50));
+        drop(inbox);
+        assert_eq!(sender.join().unwrap(), Err(TrySendError::Disconnected(2)));
+    }
+
+    #[test]
+    fn bypass_skips_the_bound_but_keeps_order_behind_the_queue() {
+        let (tx, inbox) = Channel::bounded(1, Overflow::drop_newest()).open();
+        tx.send(1, false).unwrap();
+        tx.mpsc().send(2).unwrap();
+        tx.mpsc().send(3).unwrap();
+        assert_eq!(inbox.len(), 3);
+        assert_eq!(inbox.drain(), [1, 2, 3]);
+    }
+}
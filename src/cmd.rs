@@ -0,0 +1,478 @@
+//! First-class, composable commands.
+
+use std::convert::Infallible;
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::cancel::key_of;
+use crate::{CancelToken, ChaiSender};
+
+pub(crate) type Task<Msg> = Box<dyn FnOnce(CancelToken) -> Msg + Send>;
+
+#[cfg(feature = "tokio")]
+pub(crate) type BoxFuture<Msg> = std::pin::Pin<Box<dyn std::future::Future<Output = Msg> + Send>>;
+
+#[cfg(feature = "tokio")]
+pub(crate) type FutureTask<Msg> = Box<dyn FnOnce(CancelToken) -> BoxFuture<Msg> + Send>;
+
+pub(crate) enum Effect<Msg, C> {
+    /// Run off the UI thread, then deliver the message.
+    Perform(Task<Msg>),
+    /// Poll on the runtime's tokio executor, then deliver the message.
+    #[cfg(feature = "tokio")]
+    Future(FutureTask<Msg>),
+    /// Handed to the app's own `run_cmd`.
+    User(C),
+    /// Deliver the message once the instant has passed.
+    Deliver(Instant, Msg),
+    /// Send to the window on the frame this command is run.
+    Viewport(egui::ViewportCommand),
+    /// Cancel whatever is running under this key.
+    Cancel(u64),
+}
+
+/// An effect, and the key it runs under if it can be cancelled or replaced.
+pub(crate) struct Entry<Msg, C> {
+    pub(crate) key: Option<u64>,
+    pub(crate) effect: Effect<Msg, C>,
+}
+
+impl<Msg, C> From<Effect<Msg, C>> for Entry<Msg, C> {
+    fn from(effect: Effect<Msg, C>) -> Self {
+        Self { key: None, effect }
+    }
+}
+
+/// A description of side effects for the runtime to carry out after `update`.
+///
+/// `update` returns a `Cmd` alongside the new model instead of doing work itself.
+/// Commands compose: [`batch`](Cmd::batch) runs several at once and [`map`](Cmd::map)
+/// lifts a child module's commands into its parent's message type.
+///
+/// `C` is an optional app-defined command type, handled by your `run_cmd` exactly like
+/// before. Apps that only use library-provided effects leave it at its default and pass
+/// [`no_run_cmd`] as their `run_cmd`. Anything that returned `Vec<C>` or `Option<C>`
+/// converts into a `Cmd` automatically.
+///
+/// # Example
+/// ```
+/// use chai_tea::Cmd;
+///
+/// enum Msg { Load, Loaded(usize) }
+/// struct Model { len: usize }
+///
+/// fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+///     match msg {
+///         Msg::Load => (m, Cmd::perform(|| "some file".len(), Msg::Loaded)),
+///         Msg::Loaded(len) => (Model { len }, Cmd::none()),
+///     }
+/// }
+/// ```
+pub struct Cmd<Msg, C = Infallible> {
+    pub(crate) effects: Vec<Entry<Msg, C>>,
+}
+
+impl<Msg, C> Default for Cmd<Msg, C> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<Msg, C> Cmd<Msg, C> {
+    /// Do nothing.
+    pub fn none() -> Self {
+        Self {
+            effects: Vec::new(),
+        }
+    }
+
+    /// Run several commands. There is no ordering guarantee between them.
+    pub fn batch(cmds: impl IntoIterator<Item = Cmd<Msg, C>>) -> Self {
+        Self {
+            effects: cmds.into_iter().flat_map(|cmd| cmd.effects).collect(),
+        }
+    }
+
+    /// Hand an app-defined command to `run_cmd`.
+    pub fn user(cmd: C) -> Self {
+        Self {
+            effects: vec![Effect::User(cmd).into()],
+        }
+    }
+
+    /// Deliver `msg` once `delay` has passed.
+    ///
+    /// Delays are kept by the runtime, which repaints when the next one is due, so no thread
+    /// sleeps for them. Key it with [`with_key`](Cmd::with_key) to cancel it or to restart the
+    /// delay by issuing it again.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use chai_tea::{Cmd, Simulator};
+    ///
+    /// enum Msg { Save, HideToast }
+    ///
+    /// fn update(toast: bool, msg: Msg) -> (bool, Cmd<Msg>) {
+    ///     match msg {
+    ///         Msg::Save => (true, Cmd::after(Duration::from_millis(20), Msg::HideToast).with_key("toast")),
+    ///         Msg::HideToast => (false, Cmd::none()),
+    ///     }
+    /// }
+    ///
+    /// let mut sim = Simulator::new(|| (false, Cmd::none()), || (), update, chai_tea::no_run_cmd);
+    /// sim.dispatch(Msg::Save);
+    /// sim.step();
+    /// assert!(*sim.model());
+    /// assert!(sim.step_until(Duration::from_secs(1), |toast| !toast));
+    /// ```
+    pub fn after(delay: Duration, msg: Msg) -> Self {
+        Self::at(Instant::now() + delay, msg)
+    }
+
+    /// Deliver `msg` once `deadline` has passed, like [`after`](Cmd::after).
+    pub fn at(deadline: Instant, msg: Msg) -> Self {
+        Self {
+            effects: vec![Effect::Deliver(deadline, msg).into()],
+        }
+    }
+
+    /// Send `command` to the window, e.g. to move it or change its icon.
+    ///
+    /// Like every viewport command it takes effect at the end of the frame it's run in. The
+    /// helpers below cover the common ones.
+    ///
+    /// # Example
+    /// ```
+    /// use chai_tea::Cmd;
+    ///
+    /// struct Model { level: u32, presenting: bool }
+    /// enum Msg { LevelUp, Present(bool) }
+    ///
+    /// fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+    ///     match msg {
+    ///         Msg::LevelUp => {
+    ///             let level = m.level + 1;
+    ///             (Model { level, ..m }, Cmd::set_title(format!("Level {level}")))
+    ///         }
+    ///         Msg::Present(on) => (
+    ///             Model { presenting: on, ..m },
+    ///             Cmd::batch([Cmd::fullscreen(on), Cmd::always_on_top(on)]),
+    ///         ),
+    ///     }
+    /// }
+    /// ```
+    pub fn viewport(command: egui::ViewportCommand) -> Self {
+        Self {
+            effects: vec![Effect::Viewport(command).into()],
+        }
+    }
+
+    /// Change the window title.
+    pub fn set_title(title: impl Into<String>) -> Self {
+        Self::viewport(egui::ViewportCommand::Title(title.into()))
+    }
+
+    /// Resize the window's inner area, in points.
+    pub fn resize(size: impl Into<egui::Vec2>) -> Self {
+        Self::viewport(egui::ViewportCommand::InnerSize(size.into()))
+    }
+
+    /// Make the window fullscreen, or bring it back from fullscreen.
+    pub fn fullscreen(fullscreen: bool) -> Self {
+        Self::viewport(egui::ViewportCommand::Fullscreen(fullscreen))
+    }
+
+    /// Keep the window above all others, or stop doing so.
+    pub fn always_on_top(on_top: bool) -> Self {
+        Self::viewport(egui::ViewportCommand::WindowLevel(match on_top {
+            true => egui::WindowLevel::AlwaysOnTop,
+            false => egui::WindowLevel::Normal,
+        }))
+    }
+
+    /// Close the window. This goes through [`WindowEvent::CloseRequested`](crate::WindowEvent::CloseRequested)
+    /// like any other close.
+    pub fn close() -> Self {
+        Self::viewport(egui::ViewportCommand::Close)
+    }
+
+    /// Keep the window open after a [`WindowEvent::CloseRequested`](crate::WindowEvent::CloseRequested),
+    /// e.g. to ask about unsaved work first.
+    ///
+    /// Only works when returned from the `update` that handled the close request, which happens
+    /// in the same frame.
+    pub fn cancel_close() -> Self {
+        Self::viewport(egui::ViewportCommand::CancelClose)
+    }
+
+    /// Cancel the command running under `id`, if any. See [`with_key`](Cmd::with_key).
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use chai_tea::{Cmd, Simulator};
+    ///
+    /// #[derive(Default)]
+    /// struct Model { done: bool }
+    /// enum Msg { Start, Stop, Done }
+    ///
+    /// fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+    ///     match msg {
+    ///         Msg::Start => {
+    ///             let slow = |token: chai_tea::CancelToken| token.sleep(Duration::from_secs(5));
+    ///             (m, Cmd::perform_cancellable(slow, |_| Msg::Done).with_key("slow"))
+    ///         }
+    ///         Msg::Stop => (m, Cmd::cancel("slow")),
+    ///         Msg::Done => (Model { done: true }, Cmd::none()),
+    ///     }
+    /// }
+    ///
+    /// let mut sim = Simulator::new(|| (Model::default(), Cmd::none()), || (), update, chai_tea::no_run_cmd);
+    /// sim.dispatch(Msg::Start);
+    /// sim.step();
+    /// sim.dispatch(Msg::Stop);
+    /// sim.step();
+    /// assert!(!sim.step_until(Duration::from_millis(100), |m| m.done));
+    /// ```
+    pub fn cancel(id: impl Hash) -> Self {
+        Self {
+            effects: vec![Effect::Cancel(key_of(id)).into()],
+        }
+    }
+
+    /// Run this command under `id`, so it can be cancelled with [`Cmd::cancel`].
+    ///
+    /// Starting a keyed command cancels whatever is still running under the same key, so
+    /// re-issuing e.g. a search replaces the previous one. A cancelled command's
+    /// [`CancelToken`] is raised, its pending futures are dropped, and its result is discarded.
+    /// `run_cmd` gets the token through [`ChaiSender::cancel_token`].
+    ///
+    /// # Example
+    /// ```
+    /// # use chai_tea::Cmd;
+    /// enum Msg { Search(String), Results(Vec<String>), StopSearch }
+    ///
+    /// fn search(query: &str) -> Vec<String> { vec![query.to_uppercase()] }
+    ///
+    /// fn update(m: (), msg: Msg) -> ((), Cmd<Msg>) {
+    ///     match msg {
+    ///         Msg::Search(q) => (m, Cmd::perform(move || search(&q), Msg::Results).with_key("search")),
+    ///         Msg::StopSearch => (m, Cmd::cancel("search")),
+    ///         Msg::Results(_) => (m, Cmd::none()),
+    ///     }
+    /// }
+    /// ```
+    pub fn with_key(mut self, id: impl Hash) -> Self {
+        let key = key_of(id);
+        for entry in &mut self.effects {
+            if !matches!(entry.effect, Effect::Cancel(_)) {
+                entry.key = Some(key);
+            }
+        }
+        self
+    }
+
+    /// Whether this command does nothing.
+    pub fn is_none(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// The app-defined commands this will hand to `run_cmd`, for assertions in tests.
+    pub fn user_cmds(&self) -> impl Iterator<Item = &C> {
+        self.effects.iter().filter_map(|entry| match &entry.effect {
+            Effect::User(cmd) => Some(cmd),
+            _ => None,
+        })
+    }
+
+    /// Append another command's effects to this one.
+    pub(crate) fn append(&mut self, mut other: Self) {
+        self.effects.append(&mut other.effects);
+    }
+
+    /// Move every key under `scope`, so identical keys from different scopes don't collide.
+    pub(crate) fn scoped(mut self, scope: u64) -> Self {
+        for entry in &mut self.effects {
+            entry.key = entry.key.map(|key| key_of((scope, key)));
+            if let Effect::Cancel(key) = &mut entry.effect {
+                *key = key_of((scope, *key));
+            }
+        }
+        self
+    }
+}
+
+impl<Msg> Cmd<Msg> {
+    /// Use this command where app-defined commands of type `C` are expected; it has none.
+    pub(crate) fn with_user_type<C>(self) -> Cmd<Msg, C> {
+        Cmd {
+            effects: self
+                .effects
+                .into_iter()
+                .map(|Entry { key, effect }| {
+                    let effect = match effect {
+                        Effect::Perform(task) => Effect::Perform(task),
+                        #[cfg(feature = "tokio")]
+                        Effect::Future(future) => Effect::Future(future),
+                        Effect::User(cmd) => match cmd {},
+                        Effect::Deliver(at, msg) => Effect::Deliver(at, msg),
+                        Effect::Viewport(command) => Effect::Viewport(command),
+                        Effect::Cancel(key) => Effect::Cancel(key),
+                    };
+                    Entry { key, effect }
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<Msg: Send + 'static, C> Cmd<Msg, C> {
+    /// Run `task` off the UI thread and deliver its result as `to_msg(result)`.
+    ///
+    /// The message has to be `Send` to come back, so apps started with
+    /// [`run_local`](crate::AppBuilder::run_local) for messages that aren't can't build this,
+    /// nor [`future`](Cmd::future).
+    pub fn perform<T, Ftask, Fmsg>(task: Ftask, to_msg: Fmsg) -> Self
+    where
+        Ftask: FnOnce() -> T + Send + 'static,
+        Fmsg: FnOnce(T) -> Msg + Send + 'static,
+    {
+        Self::perform_cancellable(move |_| task(), to_msg)
+    }
+
+    /// Like [`perform`](Cmd::perform), but `task` gets the command's [`CancelToken`] to check
+    /// or sleep on. Only keyed commands are ever cancelled.
+    pub fn perform_cancellable<T, Ftask, Fmsg>(task: Ftask, to_msg: Fmsg) -> Self
+    where
+        Ftask: FnOnce(CancelToken) -> T + Send + 'static,
+        Fmsg: FnOnce(T) -> Msg + Send + 'static,
+    {
+        Self {
+            effects: vec![Effect::Perform(Box::new(move |token| to_msg(task(token)))).into()],
+        }
+    }
+
+    /// Run `future` on chai-tea's tokio runtime and deliver the message it resolves to.
+    ///
+    /// The runtime is started on first use and shut down when the window closes, so there is
+    /// no need for `#[tokio::main]`.
+    #[cfg(feature = "tokio")]
+    pub fn future<F>(future: F) -> Self
+    where
+        F: std::future::Future<Output = Msg> + Send + 'static,
+    {
+        Self::future_cancellable(move |_| future)
+    }
+
+    /// Like [`future`](Cmd::future), but the future is built from the command's [`CancelToken`],
+    /// e.g. to await [`CancelToken::cancelled`] for cleanup. Only keyed commands are ever cancelled.
+    #[cfg(feature = "tokio")]
+    pub fn future_cancellable<F, Ffuture>(future: Ffuture) -> Self
+    where
+        F: std::future::Future<Output = Msg> + Send + 'static,
+        Ffuture: FnOnce(CancelToken) -> F + Send + 'static,
+    {
+        Self {
+            effects: vec![
+                Effect::Future(Box::new(move |token| -> BoxFuture<Msg> {
+                    Box::pin(future(token))
+                }))
+                .into(),
+            ],
+        }
+    }
+
+    /// Run a fallible `future` like [`future`](Cmd::future), mapping its result to a message.
+    ///
+    /// # Example
+    /// ```
+    /// # use chai_tea::Cmd;
+    /// enum Msg { Fetched(String), Failed(String) }
+    ///
+    /// async fn fetch() -> Result<String, std::io::Error> { Ok("hi".into()) }
+    ///
+    /// let cmd: Cmd<Msg> = Cmd::attempt(fetch(), Msg::Fetched, |e| Msg::Failed(e.to_string()));
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn attempt<T, E, F, Fok, Ferr>(future: F, on_ok: Fok, on_err: Ferr) -> Self
+    where
+        F: std::future::Future<Output = Result<T, E>> + Send + 'static,
+        Fok: FnOnce(T) -> Msg + Send + 'static,
+        Ferr: FnOnce(E) -> Msg + Send + 'static,
+    {
+        Self::future(async move {
+            match future.await {
+                Ok(value) => on_ok(value),
+                Err(err) => on_err(err),
+            }
+        })
+    }
+}
+
+impl<Msg: 'static, C> Cmd<Msg, C> {
+    /// Turn every message this command produces into a different message type.
+    ///
+    /// This is how a parent embeds a child's commands: `child_cmd.map(ParentMsg::Child)`.
+    pub fn map<Msg2, F>(self, f: F) -> Cmd<Msg2, C>
+    where
+        Msg2: 'static,
+        F: Fn(Msg) -> Msg2 + Send + Sync + 'static,
+    {
+        let f = Arc::new(f);
+        Cmd {
+            effects: self
+                .effects
+                .into_iter()
+                .map(|Entry { key, effect }| {
+                    let effect = match effect {
+                        Effect::Perform(task) => {
+                            let f = Arc::clone(&f);
+                            Effect::Perform(Box::new(move |token| f(task(token))))
+                        }
+                        #[cfg(feature = "tokio")]
+                        Effect::Future(future) => {
+                            let f = Arc::clone(&f);
+                            Effect::Future(Box::new(move |token| -> BoxFuture<Msg2> {
+                                let future = future(token);
+                                Box::pin(async move { f(future.await) })
+                            }))
+                        }
+                        Effect::User(cmd) => Effect::User(cmd),
+                        Effect::Deliver(at, msg) => Effect::Deliver(at, f(msg)),
+                        Effect::Viewport(command) => Effect::Viewport(command),
+                        Effect::Cancel(key) => Effect::Cancel(key),
+                    };
+                    Entry { key, effect }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Lets an in-place `update` that issues no commands return nothing.
+impl<Msg, C> From<()> for Cmd<Msg, C> {
+    fn from((): ()) -> Self {
+        Self::none()
+    }
+}
+
+impl<Msg, C> From<Vec<C>> for Cmd<Msg, C> {
+    fn from(cmds: Vec<C>) -> Self {
+        Self::batch(cmds.into_iter().map(Self::user))
+    }
+}
+
+impl<Msg, C> From<Option<C>> for Cmd<Msg, C> {
+    fn from(cmd: Option<C>) -> Self {
+        Self::batch(cmd.map(Self::user))
+    }
+}
+
+/// The `run_cmd` for apps whose commands are all [`Cmd`]s, with no command type of their own.
+pub fn no_run_cmd<S, Msg>(cmd: Infallible, _sync_state: &mut S, _tx: ChaiSender<Msg>) {
+    match cmd {}
+}
@@ -0,0 +1,78 @@
+//! Dropping messages that a newer one in the same frame makes redundant.
+
+use std::collections::{HashSet, VecDeque};
+use std::hash::Hash;
+
+use crate::cancel::key_of;
+
+pub(crate) type CoalesceFn<Msg> = Box<dyn Fn(&Msg) -> Option<u64>>;
+
+/// Hash the keys `key` picks, so the runtime can hold it without knowing their type.
+pub(crate) fn keyed<Msg, K, F>(key: F) -> CoalesceFn<Msg>
+where
+    K: Hash,
+    F: Fn(&Msg) -> Option<K> + 'static,
+{
+    Box::new(move |msg| key(msg).map(key_of))
+}
+
+/// Keep only the newest message of each key, where it stands in the queue. Unkeyed messages
+/// are all kept.
+pub(crate) fn keep_newest<Msg>(msgs: &mut VecDeque<Msg>, key: &CoalesceFn<Msg>) {
+    let mut seen = HashSet::new();
+    let mut kept = VecDeque::with_capacity(msgs.len());
+    while let Some(msg) = msgs.pop_back() {
+        if key(&msg).is_none_or(|key| seen.insert(key)) {
+            kept.push_front(msg);
+        }
+    }
+    *msgs = kept;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coalesced(msgs: &[(Option<char>, u32)]) -> Vec<(Option<char>, u32)> {
+        let mut msgs: VecDeque<_> = msgs.iter().copied().collect();
+        keep_newest(&mut msgs, &keyed(|(key, _): &(Option<char>, u32)| *key));
+        msgs.into()
+    }
+
+    #[test]
+    fn keeps_the_newest_of_each_key_where_it_stands() {
+        let msgs = [
+            (Some('a'), 1),
+            (Some('b'), 2),
+            (Some('a'), 3),
+            (Some('c'), 4),
+            (Some('b'), 5),
+        ];
+        assert_eq!(
+            coalesced(&msgs),
+            [(Some('a'), 3), (Some('c'), 4), (Some('b'), 5)]
+        );
+    }
+
+    #[test]
+    fn keeps_every_unkeyed_message() {
+        let msgs = [
+            (None, 1),
+            (Some('a'), 2),
+            (None, 3),
+            (Some('a'), 4),
+            (None, 5),
+        ];
+        assert_eq!(
+            coalesced(&msgs),
+            [(None, 1), (None, 3), (Some('a'), 4), (None, 5)]
+        );
+    }
+
+    #[test]
+    fn leaves_distinct_keys_alone() {
+        let msgs = [(Some('a'), 1), (Some('b'), 2), (Some('c'), 3)];
+        assert_eq!(coalesced(&msgs), msgs);
+        assert_eq!(coalesced(&[]), []);
+    }
+}
@@ -0,0 +1,216 @@
+//! Reusable pieces of UI with their own model, messages, update and view.
+
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use eframe::egui;
+
+use crate::Cmd;
+use crate::cancel::key_of;
+
+/// Anything a view can push messages into.
+///
+/// The root `view` pushes into its `Vec<Msg>`; an embedded [`Component`] gets a dispatcher that
+/// lifts its messages into the parent's on the way in.
+pub trait Dispatch<Msg> {
+    /// Queue `msg` for `update`, as pushing onto the root view's `Vec` does.
+    fn push(&mut self, msg: Msg);
+}
+
+impl<Msg> Dispatch<Msg> for Vec<Msg> {
+    fn push(&mut self, msg: Msg) {
+        Vec::push(self, msg);
+    }
+}
+
+/// A dispatcher that lifts child messages before handing them to the parent's.
+struct Lifted<'a, Msg, ChildMsg> {
+    parent: &'a mut dyn Dispatch<Msg>,
+    lift: fn(ChildMsg) -> Msg,
+}
+
+impl<Msg, ChildMsg> Dispatch<ChildMsg> for Lifted<'_, Msg, ChildMsg> {
+    fn push(&mut self, msg: ChildMsg) {
+        self.parent.push((self.lift)(msg));
+    }
+}
+
+/// A self-contained part of an app: its own model, messages, update and view.
+///
+/// A component knows nothing about the app it lives in. A parent keeps the component's model
+/// in its own, wraps its messages in one of its own variants, and talks to it through an
+/// [`Embed`], which lifts messages and commands in both directions.
+///
+/// Components issue only the commands chai-tea provides, a [`Cmd`] with no app-defined type: an
+/// app's own commands belong to its `run_cmd`, which a component can't know about. A component
+/// that needs one sends a message its parent answers with the command.
+pub trait Component {
+    type Model;
+    type Msg: 'static;
+
+    fn update(model: Self::Model, msg: Self::Msg) -> (Self::Model, Cmd<Self::Msg>);
+
+    /// Draw into the parent's `ui`, pushing messages into `tx`.
+    fn view(model: &Self::Model, ui: &mut egui::Ui, tx: &mut dyn Dispatch<Self::Msg>);
+}
+
+/// One instance of a [`Component`] inside a parent whose messages are `Msg`.
+///
+/// `lift` wraps the child's messages in the parent's, typically an enum variant such as
+/// `Msg::Left`. The `id` names the instance: keyed commands the child issues are scoped to it,
+/// so two instances of the same component never cancel each other's work.
+///
+/// `Embed`s are cheap, so it's fine to build one wherever it's needed.
+///
+/// # Example
+/// ```
+/// use chai_tea::{Cmd, Component, Dispatch, Embed, Simulator};
+/// use eframe::egui;
+///
+/// struct Toggle;
+///
+/// impl Component for Toggle {
+///     type Model = bool;
+///     type Msg = ();
+///
+///     fn update(on: bool, _: ()) -> (bool, Cmd<()>) {
+///         (!on, Cmd::none())
+///     }
+///
+///     fn view(on: &bool, ui: &mut egui::Ui, tx: &mut dyn Dispatch<()>) {
+///         if ui.button(if *on { "on" } else { "off" }).clicked() {
+///             tx.push(());
+///         }
+///     }
+/// }
+///
+/// #[derive(Default)]
+/// struct Model { left: bool, right: bool }
+/// enum Msg { Left(()), Right(()) }
+///
+/// fn left() -> Embed<Toggle, Msg> { Embed::new("left", Msg::Left) }
+/// fn right() -> Embed<Toggle, Msg> { Embed::new("right", Msg::Right) }
+///
+/// fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+///     match msg {
+///         Msg::Left(msg) => {
+///             let (left, cmd) = left().update(m.left, msg);
+///             (Model { left, ..m }, cmd)
+///         }
+///         Msg::Right(msg) => {
+///             let (right, cmd) = right().update(m.right, msg);
+///             (Model { right, ..m }, cmd)
+///         }
+///     }
+/// }
+///
+/// fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) {
+///     egui::CentralPanel::default().show(ctx, |ui| {
+///         left().view(&m.left, ui, tx);
+///         right().view(&m.right, ui, tx);
+///     });
+/// }
+///
+/// let mut sim = Simulator::new(|| (Model::default(), Cmd::none()), || (), update, chai_tea::no_run_cmd);
+/// sim.dispatch(Msg::Right(()));
+/// sim.step();
+/// assert!(!sim.model().left && sim.model().right);
+/// ```
+pub struct Embed<Child: Component, Msg> {
+    scope: u64,
+    lift: fn(Child::Msg) -> Msg,
+    child: PhantomData<Child>,
+}
+
+impl<Child: Component, Msg: 'static> Embed<Child, Msg> {
+    /// The instance named `id`, whose messages the parent wraps with `lift`.
+    pub fn new(id: impl Hash, lift: fn(Child::Msg) -> Msg) -> Self {
+        Self {
+            scope: key_of(id),
+            lift,
+            child: PhantomData,
+        }
+    }
+
+    /// Run the child's `update`, lifting its commands into the parent's.
+    pub fn update<C>(&self, model: Child::Model, msg: Child::Msg) -> (Child::Model, Cmd<Msg, C>) {
+        let (model, cmd) = Child::update(model, msg);
+        let cmd = cmd.scoped(self.scope).map(self.lift).with_user_type();
+        (model, cmd)
+    }
+
+    /// Run the child's `view` with a dispatcher that lifts its messages into `tx`.
+    pub fn view(&self, model: &Child::Model, ui: &mut egui::Ui, tx: &mut dyn Dispatch<Msg>) {
+        let mut tx = Lifted {
+            parent: tx,
+            lift: self.lift,
+        };
+        Child::view(model, ui, &mut tx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::Simulator;
+
+    /// Finishes a job a moment after it starts, unless it's stopped first.
+    struct Job;
+
+    enum JobMsg {
+        Start,
+        Stop,
+        Done,
+    }
+
+    impl Component for Job {
+        type Model = u32;
+        type Msg = JobMsg;
+
+        fn update(done: u32, msg: JobMsg) -> (u32, Cmd<JobMsg>) {
+            match msg {
+                JobMsg::Start => (
+                    done,
+                    Cmd::after(Duration::from_millis(5), JobMsg::Done).with_key("job"),
+                ),
+                JobMsg::Stop => (done, Cmd::cancel("job")),
+                JobMsg::Done => (done + 1, Cmd::none()),
+            }
+        }
+
+        fn view(_: &u32, _: &mut egui::Ui, _: &mut dyn Dispatch<JobMsg>) {}
+    }
+
+    enum Msg {
+        Left(JobMsg),
+        Right(JobMsg),
+    }
+
+    fn update((left, right): (u32, u32), msg: Msg) -> ((u32, u32), Cmd<Msg>) {
+        match msg {
+            Msg::Left(msg) => {
+                let (left, cmd) = Embed::<Job, _>::new("left", Msg::Left).update(left, msg);
+                ((left, right), cmd)
+            }
+            Msg::Right(msg) => {
+                let (right, cmd) = Embed::<Job, _>::new("right", Msg::Right).update(right, msg);
+                ((left, right), cmd)
+            }
+        }
+    }
+
+    #[test]
+    fn instances_dont_cancel_each_others_keyed_commands() {
+        let mut sim = Simulator::new(|| ((0, 0), Cmd::none()), || (), update, crate::no_run_cmd);
+        sim.dispatch(Msg::Left(JobMsg::Start));
+        sim.dispatch(Msg::Right(JobMsg::Start));
+        sim.dispatch(Msg::Right(JobMsg::Stop));
+
+        assert!(sim.step_until(Duration::from_secs(1), |&(left, _)| left == 1));
+        std::thread::sleep(Duration::from_millis(10));
+        sim.step();
+        assert_eq!(*sim.model(), (1, 0));
+    }
+}
@@ -0,0 +1,256 @@
+//! The opt-in time-travel debugger panel.
+
+use std::collections::VecDeque;
+
+use eframe::egui;
+
+/// How many snapshots the debugger keeps before dropping the oldest.
+const HISTORY_LEN: usize = 1000;
+
+struct Snapshot<M> {
+    label: String,
+    model: M,
+}
+
+/// Records the model after every update and lets you scrub back through it.
+///
+/// Scrubbing only changes which snapshot `view` renders; `update` and commands are never
+/// re-run, so side effects stay where they happened. While paused, incoming messages are
+/// held back and applied in order on resume. Messages `view` pushes while showing a past
+/// snapshot are dropped, since they were produced from a stale model. Window events always go
+/// straight through: a close request has to be answered in the frame it arrives, or the window
+/// closes without `update` getting a say.
+pub(crate) struct Debugger<M, Msg> {
+    history: VecDeque<Snapshot<M>>,
+    cursor: Option<usize>,
+    paused: bool,
+    pending: Vec<Msg>,
+    snapshot: fn(&M) -> M,
+    describe: fn(&Msg) -> String,
+}
+
+impl<M, Msg> Debugger<M, Msg> {
+    pub(crate) fn new(model: &M) -> Self
+    where
+        M: Clone,
+        Msg: std::fmt::Debug,
+    {
+        let mut debugger = Self {
+            history: VecDeque::new(),
+            cursor: None,
+            paused: false,
+            pending: Vec::new(),
+            snapshot: M::clone,
+            describe: |msg| format!("{msg:?}"),
+        };
+        debugger.push(String::from("init"), model);
+        debugger
+    }
+
+    fn push(&mut self, label: String, model: &M) {
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(Snapshot {
+            label,
+            model: (self.snapshot)(model),
+        });
+    }
+
+    /// Decide which of this frame's messages `update` should see now.
+    ///
+    /// `deferred` are the ones a budget held back from earlier frames. While paused they are
+    /// all held back, except `window_msgs`; on resume the held ones come first. View messages
+    /// are dropped while a past snapshot is shown.
+    pub(crate) fn gate(
+        &mut self,
+        deferred: Vec<Msg>,
+        mut view_msgs: Vec<Msg>,
+        window_msgs: Vec<Msg>,
+        async_msgs: Vec<Msg>,
+    ) -> Vec<Msg> {
+        if self.cursor.is_some() {
+            view_msgs.clear();
+        }
+        if self.paused {
+            let held = deferred.into_iter().chain(view_msgs).chain(async_msgs);
+            self.pending.extend(held);
+            return window_msgs;
+        }
+        let mut ready = std::mem::take(&mut self.pending);
+        ready.extend(deferred);
+        ready.extend(view_msgs);
+        ready.extend(window_msgs);
+        ready.extend(async_msgs);
+        ready
+    }
+
+    /// Label a message before `update` consumes it.
+    pub(crate) fn describe(&self, msg: &Msg) -> String {
+        (self.describe)(msg)
+    }
+
+    /// Record the model `update` produced for the message labelled `label`.
+    pub(crate) fn record(&mut self, label: String, model: &M) {
+        self.push(label, model);
+    }
+
+    /// The model `view` should render: the selected snapshot, or `live`.
+    pub(crate) fn shown<'a>(&'a self, live: &'a M) -> &'a M {
+        match self.cursor.and_then(|i| self.history.get(i)) {
+            Some(snapshot) => &snapshot.model,
+            None => live,
+        }
+    }
+
+    /// Draw the timeline panel. Call before `view`, since side panels must precede the central one.
+    pub(crate) fn show(&mut self, ctx: &egui::Context) {
+        egui::SidePanel::right("chai_tea_debugger").show(ctx, |ui| {
+            ui.heading("🍵 time travel");
+
+            ui.horizontal(|ui| {
+                if self.paused {
+                    if ui.button("▶ resume").clicked() {
+                        self.resume();
+                        ctx.request_repaint();
+                    }
+                } else if ui.button("⏸ pause").clicked() {
+                    self.paused = true;
+                }
+                ui.label(format!("{} queued", self.pending.len()));
+            });
+
+            let last = self.history.len() - 1;
+            let mut selected = self.cursor.unwrap_or(last);
+            let slider = egui::Slider::new(&mut selected, 0..=last).text("step");
+            if ui.add(slider).changed() {
+                self.scrub(selected, last);
+            }
+
+            ui.separator();
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (i, snapshot) in self.history.iter().enumerate().rev() {
+                    let label = format!("{i}: {}", snapshot.label);
+                    if ui.selectable_label(i == selected, label).clicked() {
+                        selected = i;
+                    }
+                }
+            });
+            if selected != self.cursor.unwrap_or(last) {
+                self.scrub(selected, last);
+            }
+        });
+    }
+
+    /// Go back to the live model, letting the held messages through on the next gate.
+    fn resume(&mut self) {
+        self.paused = false;
+        self.cursor = None;
+    }
+
+    /// Show snapshot `index`, pausing live processing; the latest snapshot counts as live.
+    fn scrub(&mut self, index: usize, last: usize) {
+        self.paused = true;
+        self.cursor = (index != last).then_some(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn debugger() -> Debugger<u32, &'static str> {
+        Debugger::new(&0)
+    }
+
+    fn gate(
+        debugger: &mut Debugger<u32, &'static str>,
+        view: &[&'static str],
+        window: &[&'static str],
+        background: &[&'static str],
+    ) -> Vec<&'static str> {
+        debugger.gate(
+            Vec::new(),
+            view.to_vec(),
+            window.to_vec(),
+            background.to_vec(),
+        )
+    }
+
+    #[test]
+    fn live_messages_pass_in_order() {
+        let mut debugger = debugger();
+
+        let ready = debugger.gate(vec!["held"], vec!["view"], vec!["close"], vec!["fetched"]);
+
+        assert_eq!(ready, ["held", "view", "close", "fetched"]);
+    }
+
+    #[test]
+    fn pausing_holds_messages_until_resume() {
+        let mut debugger = debugger();
+        debugger.paused = true;
+
+        assert!(gate(&mut debugger, &["a"], &[], &["b"]).is_empty());
+        assert!(gate(&mut debugger, &["c"], &[], &[]).is_empty());
+        debugger.resume();
+
+        assert_eq!(gate(&mut debugger, &["d"], &[], &[]), ["a", "b", "c", "d"]);
+        assert!(debugger.pending.is_empty());
+    }
+
+    #[test]
+    fn window_events_pass_while_paused() {
+        let mut debugger = debugger();
+        debugger.paused = true;
+
+        assert_eq!(gate(&mut debugger, &["view"], &["close"], &[]), ["close"]);
+        assert_eq!(debugger.pending, ["view"]);
+    }
+
+    #[test]
+    fn scrubbing_drops_view_messages_but_not_window_events() {
+        let mut debugger = debugger();
+        debugger.record("a".into(), &1);
+        debugger.record("b".into(), &2);
+        debugger.scrub(1, 2);
+
+        assert_eq!(*debugger.shown(&2), 1);
+        assert_eq!(
+            gate(&mut debugger, &["stale"], &["close"], &["fetched"]),
+            ["close"]
+        );
+        assert_eq!(debugger.pending, ["fetched"]);
+
+        debugger.resume();
+        assert_eq!(*debugger.shown(&2), 2);
+        assert_eq!(
+            gate(&mut debugger, &["fresh"], &[], &[]),
+            ["fetched", "fresh"]
+        );
+    }
+
+    #[test]
+    fn scrubbing_to_the_latest_snapshot_shows_the_live_model() {
+        let mut debugger = debugger();
+        debugger.record("a".into(), &1);
+        debugger.scrub(1, 1);
+
+        assert!(debugger.paused);
+        assert_eq!(*debugger.shown(&7), 7);
+        assert!(gate(&mut debugger, &["view"], &[], &[]).is_empty());
+        assert_eq!(debugger.pending, ["view"]);
+    }
+
+    #[test]
+    fn history_keeps_the_newest_snapshots() {
+        let mut debugger = debugger();
+        for n in 1..=HISTORY_LEN as u32 {
+            debugger.record(format!("{n}"), &n);
+        }
+
+        assert_eq!(debugger.history.len(), HISTORY_LEN);
+        assert_eq!(debugger.history.front().unwrap().label, "1");
+        assert_eq!(debugger.history.back().unwrap().model, HISTORY_LEN as u32);
+    }
+}
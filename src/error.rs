@@ -0,0 +1,106 @@
+//! Failures an app recovers from on its own, reported to [`AppBuilder::on_error`](crate::AppBuilder::on_error).
+
+use std::fmt;
+#[cfg(feature = "serde")]
+use std::io;
+
+/// Something that went wrong without stopping the app.
+///
+/// Each variant says how the app carried on. Errors are dropped unless an
+/// [`on_error`](crate::AppBuilder::on_error) hook is set.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A [`Cmd::perform`](crate::Cmd::perform) or [`Cmd::future`](crate::Cmd::future) reached an
+    /// app started with [`run_local`](crate::AppBuilder::run_local), which has no way to bring
+    /// its message back to the UI thread, so it never ran.
+    CommandDropped { command: &'static str },
+    /// A message couldn't be serialized for the [`Recorder`](crate::Recorder), so it was left
+    /// out of the log. Recording carries on with the next one.
+    #[cfg(feature = "serde")]
+    UnrecordableMessage { source: serde_json::Error },
+    /// Writing to the [`Recorder`](crate::Recorder)'s log failed, so recording stopped.
+    #[cfg(feature = "serde")]
+    RecordingStopped { source: io::Error },
+    /// The save under `key` couldn't be read, so the model `init` returned was kept.
+    #[cfg(feature = "persistence")]
+    UnreadableSave {
+        key: String,
+        source: serde_json::Error,
+    },
+    /// The save under `key` has schema version `from`, and no migration from it to `to` is
+    /// registered, so the model `init` returned was kept.
+    #[cfg(feature = "persistence")]
+    NoMigration { key: String, from: u32, to: u32 },
+    /// The save under `key`, at schema version `version`, didn't fit the persisted type, so the
+    /// model `init` returned was kept.
+    #[cfg(feature = "persistence")]
+    BadSave {
+        key: String,
+        version: u32,
+        source: serde_json::Error,
+    },
+    /// The model couldn't be serialized to save under `key`; the previous save is left as it was.
+    #[cfg(feature = "persistence")]
+    Save {
+        key: String,
+        source: serde_json::Error,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::CommandDropped { command } => {
+                write!(
+                    f,
+                    "dropped {command}: run_local can't run it off the UI thread"
+                )
+            }
+            #[cfg(feature = "serde")]
+            Error::UnrecordableMessage { source } => {
+                write!(f, "can't record message, skipping it: {source}")
+            }
+            #[cfg(feature = "serde")]
+            Error::RecordingStopped { source } => write!(f, "recording stopped: {source}"),
+            #[cfg(feature = "persistence")]
+            Error::UnreadableSave { key, source } => {
+                write!(f, "ignoring unreadable saved model {key:?}: {source}")
+            }
+            #[cfg(feature = "persistence")]
+            Error::NoMigration { key, from, to } => write!(
+                f,
+                "ignoring saved model {key:?}: no migration from version {from} to {to}"
+            ),
+            #[cfg(feature = "persistence")]
+            Error::BadSave {
+                key,
+                version,
+                source,
+            } => write!(
+                f,
+                "ignoring saved model {key:?} (version {version}): {source}"
+            ),
+            #[cfg(feature = "persistence")]
+            Error::Save { key, source } => write!(f, "can't save model {key:?}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::CommandDropped { .. } => None,
+            #[cfg(feature = "serde")]
+            Error::UnrecordableMessage { source } => Some(source),
+            #[cfg(feature = "serde")]
+            Error::RecordingStopped { source } => Some(source),
+            #[cfg(feature = "persistence")]
+            Error::UnreadableSave { source, .. }
+            | Error::BadSave { source, .. }
+            | Error::Save { source, .. } => Some(source),
+            #[cfg(feature = "persistence")]
+            Error::NoMigration { .. } => None,
+        }
+    }
+}
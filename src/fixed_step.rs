@@ -0,0 +1,560 @@
+//! A simulation stepped at a fixed rate on its own thread, with the UI rendering snapshots.
+
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::Dispatch;
+#[cfg(feature = "persistence")]
+use crate::persist::Store;
+#[cfg(feature = "persistence")]
+use crate::runtime::ErrorFn;
+
+type InitFn<M> = Box<dyn FnOnce() -> M>;
+type StepFn<M> = Box<dyn Fn(M, Duration) -> M + Send>;
+type UpdateFn<M, Msg> = Box<dyn Fn(M, Msg) -> M + Send>;
+type ViewFn<M, Msg> = Box<dyn Fn(&egui::Context, &M, &mut StepControl<Msg>)>;
+
+/// Configures a fixed-step app, see [`App::fixed_step`](crate::App::fixed_step).
+pub struct FixedStepBuilder<M, Msg> {
+    title: String,
+    options: eframe::NativeOptions,
+    #[cfg(feature = "persistence")]
+    store: Option<Box<dyn Store<M>>>,
+    #[cfg(feature = "persistence")]
+    on_error: Option<ErrorFn>,
+    dt: Duration,
+    max_catch_up: u32,
+    init: Option<InitFn<M>>,
+    step: Option<StepFn<M>>,
+    update: Option<UpdateFn<M, Msg>>,
+    view: Option<ViewFn<M, Msg>>,
+}
+
+impl<M, Msg> FixedStepBuilder<M, Msg> {
+    pub(crate) fn new(title: &str, dt: Duration) -> Self {
+        assert!(!dt.is_zero(), "a fixed step needs a non-zero dt");
+        Self {
+            title: title.to_owned(),
+            options: eframe::NativeOptions::default(),
+            #[cfg(feature = "persistence")]
+            store: None,
+            #[cfg(feature = "persistence")]
+            on_error: None,
+            dt,
+            max_catch_up: 5,
+            init: None,
+            step: None,
+            update: None,
+            view: None,
+        }
+    }
+
+    /// Window and backend options for eframe.
+    pub fn options(self, options: eframe::NativeOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    /// Restore the model at startup and save the latest snapshot on exit, see
+    /// [`Persistence`](crate::Persistence).
+    #[cfg(feature = "persistence")]
+    pub fn persistence<P>(self, persistence: crate::Persistence<M, P>) -> Self
+    where
+        M: 'static,
+        P: serde::de::DeserializeOwned + 'static,
+    {
+        Self {
+            store: Some(Box::new(persistence)),
+            ..self
+        }
+    }
+
+    /// Be told when a save can't be restored or written, as
+    /// [`AppBuilder::on_error`](crate::AppBuilder::on_error) is.
+    #[cfg(feature = "persistence")]
+    pub fn on_error<F>(self, on_error: F) -> Self
+    where
+        F: Fn(crate::Error) + 'static,
+    {
+        Self {
+            on_error: Some(Box::new(on_error)),
+            ..self
+        }
+    }
+
+    /// How many steps may run back to back when the simulation falls behind. Defaults to `5`.
+    ///
+    /// Steps missed beyond that are skipped rather than made up, so a slow stretch can't snowball
+    /// into an ever-growing backlog. [`StepControl::dropped`] counts them.
+    pub fn max_catch_up(self, max_catch_up: u32) -> Self {
+        Self {
+            max_catch_up: max_catch_up.max(1),
+            ..self
+        }
+    }
+
+    /// The initial model.
+    pub fn init<F>(self, init: F) -> Self
+    where
+        F: FnOnce() -> M + 'static,
+    {
+        Self {
+            init: Some(Box::new(init)),
+            ..self
+        }
+    }
+
+    /// Advance the model by one fixed `dt`.
+    pub fn step<F>(self, step: F) -> Self
+    where
+        F: Fn(M, Duration) -> M + Send + 'static,
+    {
+        Self {
+            step: Some(Box::new(step)),
+            ..self
+        }
+    }
+
+    /// How messages change the model, applied on the simulation thread between steps.
+    pub fn update<F>(self, update: F) -> Self
+    where
+        F: Fn(M, Msg) -> M + Send + 'static,
+    {
+        Self {
+            update: Some(Box::new(update)),
+            ..self
+        }
+    }
+
+    /// How the latest snapshot is drawn, pushing messages and controls onto a [`StepControl`].
+    pub fn view<F>(self, view: F) -> Self
+    where
+        F: Fn(&egui::Context, &M, &mut StepControl<Msg>) + 'static,
+    {
+        Self {
+            view: Some(Box::new(view)),
+            ..self
+        }
+    }
+}
+
+/// Where the simulation is at, as of the snapshot being rendered.
+#[derive(Clone, Copy, Default)]
+struct Status {
+    paused: bool,
+    steps: u64,
+    dropped: u64,
+}
+
+/// The latest state the simulation published for the UI.
+struct Published<M> {
+    model: Arc<M>,
+    status: Status,
+}
+
+/// What the UI sends the simulation thread.
+enum Control<Msg> {
+    Msg(Msg),
+    Pause,
+    Resume,
+    Step,
+}
+
+/// What `view` gets instead of a `Vec<Msg>` in a fixed-step app.
+///
+/// Messages pushed here are applied on the simulation thread between steps, in order. It also
+/// pauses, resumes and single-steps the simulation, and reports where it's at.
+pub struct StepControl<Msg> {
+    controls: Vec<Control<Msg>>,
+    status: Status,
+}
+
+impl<Msg> StepControl<Msg> {
+    /// Queue `msg` for `update`, as `tx.push` does in other apps.
+    pub fn push(&mut self, msg: Msg) {
+        self.controls.push(Control::Msg(msg));
+    }
+
+    /// Stop stepping. Messages are still applied while paused.
+    pub fn pause(&mut self) {
+        self.controls.push(Control::Pause);
+    }
+
+    /// Start stepping again, from now rather than catching up on the pause.
+    pub fn resume(&mut self) {
+        self.controls.push(Control::Resume);
+    }
+
+    /// Run exactly one step. Only has an effect while paused.
+    pub fn step(&mut self) {
+        self.controls.push(Control::Step);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.status.paused
+    }
+
+    /// How many steps have run.
+    pub fn steps(&self) -> u64 {
+        self.status.steps
+    }
+
+    /// How many steps were skipped because the simulation couldn't keep up.
+    pub fn dropped(&self) -> u64 {
+        self.status.dropped
+    }
+}
+
+impl<Msg> Dispatch<Msg> for StepControl<Msg> {
+    fn push(&mut self, msg: Msg) {
+        StepControl::push(self, msg);
+    }
+}
+
+impl<M, Msg> FixedStepBuilder<M, Msg>
+where
+    M: Clone + Send + Sync + 'static,
+    Msg: Send + 'static,
+{
+    /// Open the window and run the simulation until it is closed.
+    ///
+    /// `step` advances the model by one fixed `dt`. Messages from `view` go through `update` on
+    /// the simulation thread, between steps, so the model is only ever touched there. After each
+    /// batch of steps the model is cloned and published, and the window repaints to show it;
+    /// `view` never waits on the simulation, and the simulation never waits on rendering.
+    ///
+    /// The thread stops when the window closes.
+    ///
+    /// # Panics
+    /// If [`init`](FixedStepBuilder::init), [`step`](FixedStepBuilder::step),
+    /// [`update`](FixedStepBuilder::update) or [`view`](FixedStepBuilder::view) was never set.
+    pub fn run(self) -> eframe::Result<()> {
+        let Self {
+            title,
+            options,
+            #[cfg(feature = "persistence")]
+            store,
+            #[cfg(feature = "persistence")]
+            on_error,
+            dt,
+            max_catch_up,
+            init,
+            step,
+            update,
+            view,
+        } = self;
+        let init = init.expect("a fixed-step app needs an .init()");
+        let step = step.expect("a fixed-step app needs a .step()");
+        let update = update.expect("a fixed-step app needs an .update()");
+        let view = view.expect("a fixed-step app needs a .view()");
+
+        eframe::run_native(
+            &title,
+            options,
+            Box::new(move |cc| {
+                #[allow(unused_mut)]
+                let mut model = init();
+                #[cfg(feature = "persistence")]
+                if let Some(store) = &store
+                    && let Err(err) = store.restore(&mut model, cc.storage)
+                    && let Some(on_error) = &on_error
+                {
+                    on_error(err);
+                }
+
+                let published = Arc::new(Mutex::new(Published {
+                    model: Arc::new(model.clone()),
+                    status: Status::default(),
+                }));
+                let (tx, rx) = std::sync::mpsc::channel();
+
+                let sim = Simulation {
+                    model: Some(model),
+                    step,
+                    update,
+                    clock: Clock::new(dt, max_catch_up, Instant::now()),
+                    status: Status::default(),
+                    published: Arc::clone(&published),
+                    ctx: cc.egui_ctx.clone(),
+                };
+                let thread = std::thread::Builder::new()
+                    .name(String::from("chai-tea-sim"))
+                    .spawn(move || sim.run(rx))
+                    .expect("can't spawn the simulation thread");
+
+                Ok(Box::new(FixedStepApp {
+                    published,
+                    view,
+                    tx: Some(tx),
+                    thread: Some(thread),
+                    #[cfg(feature = "persistence")]
+                    store,
+                    #[cfg(feature = "persistence")]
+                    on_error,
+                }))
+            }),
+        )
+    }
+}
+
+/// When the steps of a fixed-rate simulation are due.
+struct Clock {
+    dt: Duration,
+    max_catch_up: u32,
+    /// When the next step is due.
+    next: Instant,
+}
+
+/// What a [`Clock`] says to do at some instant.
+#[derive(Debug, PartialEq, Eq)]
+struct Due {
+    /// Steps to run now.
+    steps: u32,
+    /// Steps missed beyond the catch-up limit, to skip.
+    dropped: u64,
+}
+
+impl Clock {
+    fn new(dt: Duration, max_catch_up: u32, now: Instant) -> Self {
+        Self {
+            dt,
+            max_catch_up,
+            next: now + dt,
+        }
+    }
+
+    /// Count steps from `now`, forgetting any that were missed.
+    fn restart(&mut self, now: Instant) {
+        self.next = now + self.dt;
+    }
+
+    /// Take the steps due by `now`.
+    fn tick(&mut self, now: Instant) -> Due {
+        let mut steps = 0;
+        while self.next <= now && steps < self.max_catch_up {
+            self.next += self.dt;
+            steps += 1;
+        }
+
+        let mut dropped = 0;
+        if self.next <= now {
+            // too far behind: skip the backlog instead of chasing it
+            dropped = ((now - self.next).as_nanos() / self.dt.as_nanos() + 1) as u64;
+            self.restart(now);
+        }
+        Due { steps, dropped }
+    }
+}
+
+const TAKEN: &str = "the model is only out during a step or update";
+
+/// The simulation thread's state.
+struct Simulation<M, Msg> {
+    /// Always `Some`, except while `step` or `update` has it.
+    model: Option<M>,
+    step: StepFn<M>,
+    update: UpdateFn<M, Msg>,
+    clock: Clock,
+    status: Status,
+    published: Arc<Mutex<Published<M>>>,
+    ctx: egui::Context,
+}
+
+impl<M: Clone, Msg> Simulation<M, Msg> {
+    /// Step until the UI hangs up.
+    fn run(mut self, rx: Receiver<Control<Msg>>) {
+        let mut received = None;
+
+        loop {
+            let mut changed = false;
+
+            // apply everything that arrived since the last step
+            for control in received.take().into_iter().chain(rx.try_iter()) {
+                changed = true;
+                match control {
+                    Control::Msg(msg) => {
+                        let model = self.model.take().expect(TAKEN);
+                        self.model = Some((self.update)(model, msg));
+                    }
+                    Control::Pause => self.status.paused = true,
+                    Control::Resume => {
+                        if self.status.paused {
+                            self.status.paused = false;
+                            self.clock.restart(Instant::now());
+                        }
+                    }
+                    Control::Step => {
+                        if self.status.paused {
+                            self.advance();
+                        }
+                    }
+                }
+            }
+
+            if !self.status.paused {
+                let due = self.clock.tick(Instant::now());
+                for _ in 0..due.steps {
+                    self.advance();
+                }
+                self.status.dropped += due.dropped;
+                changed |= due.steps > 0;
+            }
+
+            if changed {
+                self.publish();
+            }
+
+            // sleep until the next step is due, waking early for input
+            let control = if self.status.paused {
+                rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                rx.recv_timeout(self.clock.next.saturating_duration_since(Instant::now()))
+            };
+            match control {
+                Ok(control) => received = Some(control),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+
+    fn advance(&mut self) {
+        let model = self.model.take().expect(TAKEN);
+        self.model = Some((self.step)(model, self.clock.dt));
+        self.status.steps += 1;
+    }
+
+    fn publish(&self) {
+        let Some(model) = &self.model else {
+            return;
+        };
+        let model = Arc::new(model.clone());
+        if let Ok(mut published) = self.published.lock() {
+            *published = Published {
+                model,
+                status: self.status,
+            };
+        }
+        self.ctx.request_repaint();
+    }
+}
+
+struct FixedStepApp<M, Msg> {
+    published: Arc<Mutex<Published<M>>>,
+    view: ViewFn<M, Msg>,
+    tx: Option<Sender<Control<Msg>>>,
+    thread: Option<JoinHandle<()>>,
+    #[cfg(feature = "persistence")]
+    store: Option<Box<dyn Store<M>>>,
+    #[cfg(feature = "persistence")]
+    on_error: Option<ErrorFn>,
+}
+
+impl<M, Msg> FixedStepApp<M, Msg> {
+    /// The latest snapshot and where the simulation was at when it was taken.
+    fn latest(&self) -> Option<(Arc<M>, Status)> {
+        let published = self.published.lock().ok()?;
+        Some((Arc::clone(&published.model), published.status))
+    }
+}
+
+impl<M, Msg> eframe::App for FixedStepApp<M, Msg> {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let Some((model, status)) = self.latest() else {
+            return;
+        };
+
+        let mut control = StepControl {
+            controls: Vec::new(),
+            status,
+        };
+        (self.view)(ctx, &model, &mut control);
+
+        if let Some(tx) = &self.tx {
+            for control in control.controls {
+                // a send error means the simulation panicked; keep showing its last state
+                tx.send(control).ok();
+            }
+        }
+    }
+
+    /// Save the latest snapshot; the simulation itself keeps running.
+    #[cfg(feature = "persistence")]
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        if let (Some(store), Some((model, _))) = (&self.store, self.latest())
+            && let Err(err) = store.save(&model, storage)
+            && let Some(on_error) = &self.on_error
+        {
+            on_error(err);
+        }
+    }
+}
+
+impl<M, Msg> Drop for FixedStepApp<M, Msg> {
+    fn drop(&mut self) {
+        // hanging up wakes the simulation thread and ends it
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DT: Duration = Duration::from_millis(10);
+
+    fn due(steps: u32, dropped: u64) -> Due {
+        Due { steps, dropped }
+    }
+
+    #[test]
+    fn steps_once_per_dt() {
+        let start = Instant::now();
+        let mut clock = Clock::new(DT, 5, start);
+
+        assert_eq!(clock.tick(start), due(0, 0));
+        assert_eq!(clock.tick(start + DT / 2), due(0, 0));
+        assert_eq!(clock.tick(start + DT), due(1, 0));
+        assert_eq!(clock.tick(start + DT * 5 / 2), due(1, 0));
+        assert_eq!(clock.tick(start + DT * 3), due(1, 0));
+    }
+
+    #[test]
+    fn catches_up_within_the_limit() {
+        let start = Instant::now();
+        let mut clock = Clock::new(DT, 5, start);
+
+        assert_eq!(clock.tick(start + DT * 4), due(4, 0));
+        // the schedule is kept, not restarted from the late tick
+        assert_eq!(clock.tick(start + DT * 5), due(1, 0));
+    }
+
+    #[test]
+    fn drops_steps_past_the_limit() {
+        let start = Instant::now();
+        let mut clock = Clock::new(DT, 3, start);
+
+        // ten steps were due: three run, the other seven are skipped
+        assert_eq!(clock.tick(start + DT * 10), due(3, 7));
+        // and the schedule starts over from the late tick
+        assert_eq!(clock.tick(start + DT * 10), due(0, 0));
+        assert_eq!(clock.tick(start + DT * 11), due(1, 0));
+    }
+
+    #[test]
+    fn restart_forgets_missed_steps() {
+        let start = Instant::now();
+        let mut clock = Clock::new(DT, 5, start);
+
+        let resumed = start + DT * 100;
+        clock.restart(resumed);
+        assert_eq!(clock.tick(resumed), due(0, 0));
+        assert_eq!(clock.tick(resumed + DT), due(1, 0));
+    }
+}
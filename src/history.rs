@@ -0,0 +1,217 @@
+//! Undo and redo, kept as snapshots of the model.
+
+use std::collections::VecDeque;
+
+/// How a message affects the undo history. Returned by the function given to [`History::new`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// An undoable step of its own.
+    New,
+    /// Part of the previous step, so one undo reverts both, e.g. consecutive keystrokes.
+    ///
+    /// Starts a new step if the previous message wasn't an edit.
+    Merge,
+    /// Not undoable, e.g. a timer tick. Undoing past it restores the model from before.
+    Skip,
+    /// Revert the last step. `update` doesn't see this message.
+    Undo,
+    /// Reapply the last undone step. `update` doesn't see this message.
+    Redo,
+}
+
+/// Undo/redo for any app, handled by the runtime since `update` is already pure.
+///
+/// Before each undoable message the model is snapshotted. Messages that the step function maps
+/// to [`Step::Undo`] and [`Step::Redo`] restore snapshots instead of reaching `update`, so
+/// `view` triggers them by pushing those messages like any other. Nothing is re-run on undo or
+/// redo, and no commands are issued for it. Any new edit clears the redo stack.
+///
+/// # Example
+/// ```
+/// use chai_tea::{History, Simulator, Step};
+///
+/// #[derive(Clone, Default)]
+/// struct Model { text: String }
+/// enum Msg { Type(char), Undo, Redo }
+///
+/// fn update(m: Model, msg: Msg) -> (Model, Vec<()>) {
+///     match msg {
+///         Msg::Type(c) => (Model { text: m.text + &c.to_string() }, vec![]),
+///         Msg::Undo | Msg::Redo => (m, vec![]),
+///     }
+/// }
+///
+/// fn step(msg: &Msg) -> Step {
+///     match msg {
+///         Msg::Type(' ') => Step::New,
+///         Msg::Type(_) => Step::Merge, // a word is one step
+///         Msg::Undo => Step::Undo,
+///         Msg::Redo => Step::Redo,
+///     }
+/// }
+/// # fn run_cmd(_: (), _: &mut (), _: chai_tea::ChaiSender<Msg>) {}
+///
+/// let mut sim = Simulator::new(|| (Model::default(), vec![]), || (), update, run_cmd)
+///     .with_history(History::new(step).depth(100));
+/// for c in "hi there".chars() {
+///     sim.dispatch(Msg::Type(c));
+/// }
+/// sim.dispatch(Msg::Undo);
+/// sim.step();
+/// assert_eq!(sim.model().text, "hi");
+///
+/// sim.dispatch(Msg::Redo);
+/// sim.step();
+/// assert_eq!(sim.model().text, "hi there");
+/// ```
+pub struct History<M, Msg> {
+    depth: usize,
+    step: Box<dyn Fn(&Msg) -> Step>,
+    snapshot: fn(&M) -> M,
+    past: VecDeque<M>,
+    future: Vec<M>,
+    editing: bool,
+}
+
+impl<M: Clone, Msg> History<M, Msg> {
+    /// Classify each message with `step`. Keeps the last 100 steps unless changed with
+    /// [`depth`](History::depth).
+    pub fn new<F>(step: F) -> Self
+    where
+        F: Fn(&Msg) -> Step + 'static,
+    {
+        Self {
+            depth: 100,
+            step: Box::new(step),
+            snapshot: M::clone,
+            past: VecDeque::new(),
+            future: Vec::new(),
+            editing: false,
+        }
+    }
+}
+
+impl<M, Msg> History<M, Msg> {
+    /// How many steps can be undone. Older ones are forgotten.
+    pub fn depth(self, depth: usize) -> Self {
+        Self { depth, ..self }
+    }
+
+    /// Undo or redo in place, or snapshot `model` before an undoable `msg` is applied to it.
+    ///
+    /// Returns whether `msg` still has to go through `update`.
+    pub(crate) fn prepare(&mut self, model: &mut M, msg: &Msg) -> bool {
+        match (self.step)(msg) {
+            Step::Undo => {
+                self.editing = false;
+                if let Some(past) = self.past.pop_back() {
+                    self.future.push(std::mem::replace(model, past));
+                }
+                false
+            }
+            Step::Redo => {
+                self.editing = false;
+                if let Some(future) = self.future.pop() {
+                    self.remember(std::mem::replace(model, future));
+                }
+                false
+            }
+            Step::Skip => true,
+            step => {
+                if step == Step::New || !self.editing {
+                    self.remember((self.snapshot)(model));
+                }
+                self.future.clear();
+                self.editing = true;
+                true
+            }
+        }
+    }
+
+    /// Push a snapshot onto the undo stack, forgetting the oldest past `depth`.
+    fn remember(&mut self, snapshot: M) {
+        self.past.push_back(snapshot);
+        while self.past.len() > self.depth {
+            self.past.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `msgs` through `history`, appending each letter that reaches `update` to the model.
+    fn type_out(history: &mut History<String, (Step, char)>, model: &mut String, msgs: &str) {
+        for c in msgs.chars() {
+            let step = match c {
+                '<' => Step::Undo,
+                '>' => Step::Redo,
+                ' ' => Step::New,
+                '.' => Step::Skip,
+                _ => Step::Merge,
+            };
+            if history.prepare(model, &(step, c)) {
+                model.push(c);
+            }
+        }
+    }
+
+    fn history() -> History<String, (Step, char)> {
+        History::new(|(step, _): &(Step, char)| *step)
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_steps() {
+        let (mut history, mut model) = (history(), String::new());
+        type_out(&mut history, &mut model, "ab cd");
+        assert_eq!(model, "ab cd");
+
+        type_out(&mut history, &mut model, "<");
+        assert_eq!(model, "ab");
+        type_out(&mut history, &mut model, "<<");
+        assert_eq!(model, "");
+        type_out(&mut history, &mut model, ">");
+        assert_eq!(model, "ab");
+        type_out(&mut history, &mut model, ">>");
+        assert_eq!(model, "ab cd");
+    }
+
+    #[test]
+    fn merges_start_a_step_after_an_undo() {
+        let (mut history, mut model) = (history(), String::new());
+        type_out(&mut history, &mut model, "ab<cd");
+        assert_eq!(model, "cd");
+        type_out(&mut history, &mut model, "<");
+        assert_eq!(model, "");
+    }
+
+    #[test]
+    fn a_new_edit_clears_redo() {
+        let (mut history, mut model) = (history(), String::new());
+        type_out(&mut history, &mut model, "ab<c>");
+        assert_eq!(model, "c");
+    }
+
+    #[test]
+    fn skipped_messages_are_undone_with_the_step_before() {
+        let (mut history, mut model) = (history(), String::new());
+        type_out(&mut history, &mut model, "ab.<");
+        assert_eq!(model, "");
+    }
+
+    #[test]
+    fn depth_caps_undo_and_redo() {
+        let mut history = history().depth(2);
+        let mut model = String::new();
+        type_out(&mut history, &mut model, "a b c");
+        // only the last two of the three steps can be undone
+        type_out(&mut history, &mut model, "<<<");
+        assert_eq!(model, "a");
+        assert_eq!(history.past.len(), 0);
+
+        type_out(&mut history, &mut model, ">>>");
+        assert_eq!(model, "a b c");
+        assert_eq!(history.past.len(), 2);
+    }
+}
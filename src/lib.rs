@@ -64,11 +64,11 @@
 //! enum Msg { Start, Stop, Tick(u64) }
 //! enum Cmd { StartTimer, StopTimer }
 //!
-//! fn update(m: Model, msg: Msg) -> (Model, Option<Cmd>) {
+//! fn update(m: Model, msg: Msg) -> (Model, Vec<Cmd>) {
 //!     match msg {
-//!         Msg::Start => (Model { running: true, ..m }, Some(Cmd::StartTimer)),
-//!         Msg::Stop  => (Model { running: false, ..m }, Some(Cmd::StopTimer)),
-//!         Msg::Tick(t) => (Model { tick: t, ..m }, None),
+//!         Msg::Start => (Model { running: true, ..m }, vec![Cmd::StartTimer]),
+//!         Msg::Stop  => (Model { running: false, ..m }, vec![Cmd::StopTimer]),
+//!         Msg::Tick(t) => (Model { tick: t, ..m }, vec![]),
 //!     }
 //! }
 //!
@@ -105,7 +105,7 @@
 //! }
 //!
 //! fn main() -> eframe::Result<()> {
-//!     chai_tea::brew_async("timer", || Model::default(), sync_state_init, update, view, run_cmd)
+//!     chai_tea::brew_async("timer", || (Model::default(), vec![]), sync_state_init, update, view, run_cmd)
 //! }
 //! ```
 //! The `tx` in run_cmd is already a cloned sender, so no need to re-clone it for use in a thread.
@@ -114,13 +114,74 @@
 //!
 //! Using tokio + reqwest + scraper, chai-tea cleanly handles real async I/O:
 //!
-//! `cargo run --example scraper`
+//! `cargo run --example scraper --features tokio`
 //!
 //! Fetches a live web page, parses HTML, and updates the UI — all while keeping a pure Elm-style architecture.
 //!
+//! With the `tokio` feature, `Cmd::future` and `Cmd::attempt` run futures on a tokio runtime
+//! that chai-tea owns and shuts down when the window closes — no `#[tokio::main]` needed.
+//!
 //! [`brew_async`] uses [`ChaiSender`], which automatically triggers `ctx.request_repaint()`
 //! whenever a background thread sends a message.
 //!
+//! ## 🧃 Composable commands
+//!
+//! Instead of your own `Cmd` enum, `update` can return a library-provided [`Cmd`]:
+//!
+//! ```no_run
+//! # use eframe::egui;
+//! use chai_tea::Cmd;
+//!
+//! #[derive(Default)]
+//! struct Model { len: usize }
+//! enum Msg { Load, Loaded(usize) }
+//!
+//! fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+//!     match msg {
+//!         Msg::Load => (m, Cmd::perform(|| std::fs::read("big.bin").map_or(0, |b| b.len()), Msg::Loaded)),
+//!         Msg::Loaded(len) => (Model { len }, Cmd::none()),
+//!     }
+//! }
+//! # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+//!
+//! fn main() -> eframe::Result<()> {
+//!     chai_tea::brew_async("loader", || (Model::default(), Cmd::none()), || (), update, view, chai_tea::no_run_cmd)
+//! }
+//! ```
+//!
+//! [`Cmd::batch`] combines commands and [`Cmd::map`] lifts a child module's commands into the
+//! parent's message type. Your own commands still work alongside: wrap them with [`Cmd::user`].
+//!
+//! ## 🏗 Builder
+//!
+//! [`run`], [`run_with_opts`] and [`run_async`] are shorthands for [`App::builder`], which takes
+//! eframe options and turns on every other feature, in any combination:
+//!
+//! ```no_run
+//! # use eframe::egui;
+//! # use chai_tea::{Cmd, Sub};
+//! # #[derive(Clone)]
+//! # struct Model;
+//! # #[derive(Debug)]
+//! # enum Msg {}
+//! # fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) { (m, Cmd::none()) }
+//! # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+//! # fn subscriptions(m: &Model) -> Sub<Msg> { Sub::none() }
+//! fn main() -> eframe::Result<()> {
+//!     chai_tea::App::builder("chai_app")
+//!         .init(|| (Model, Cmd::none()))
+//!         .update(update)
+//!         .view(view)
+//!         .subscriptions(subscriptions)
+//!         .debugger()
+//!         .options(eframe::NativeOptions {
+//!             viewport: egui::ViewportBuilder::default().with_inner_size([640.0, 480.0]),
+//!             ..Default::default()
+//!         })
+//!         .run()
+//! }
+//! ```
+//!
 //! ---
 //!
 //! ## 🪶 Design
@@ -129,9 +190,9 @@
 //! |---------|------|
 //! | `Model` | Your app state |
 //! | `Msg` | Events that mutate state |
-//! | `update` | Pure function `(Model, Msg) -> Model` *(or `(Model, Msg) -> (Model, Option<Cmd>)`)* |
+//! | `update` | Pure function `(Model, Msg) -> Model` *(or `(Model, Msg) -> (Model, Vec<Cmd>)`, or in place: `(&mut Model, Msg)`)* |
 //! | `view` | Declarative egui renderer |
-//! | `Cmd` | Background / async command |
+//! | `Cmd` | Background / async command — your own enum, or a composable [`Cmd`] |
 //! | `SyncState` | Shared threading primitives (atomics, mutexes, etc.) |
 //! | `ChaiSender` | Message sender that auto-repaints UI |
 //!
@@ -152,7 +213,7 @@
 //!
 //! ```text
 //! Model (pure state)
-//! └── update() ──> optional Cmd ─┐
+//! └── update() ──> Vec<Cmd> ─────┐
 //!                                │
 //!                           run_cmd(Cmd, &mut SyncState, ChaiSender)
 //!                                │
@@ -197,17 +258,67 @@
 
 use eframe::egui;
 
-#[derive(Default)]
-struct ChaiTeaApp<M, Msg, Fupdate, Fview> {
-    model: M,
-    messages: Vec<Msg>,
-    update: Fupdate,
-    view: Fview,
+mod app;
+mod budget;
+mod cancel;
+mod channel;
+mod cmd;
+mod coalesce;
+mod component;
+mod debugger;
+mod error;
+mod fixed_step;
+mod history;
+mod pace;
+#[cfg(feature = "persistence")]
+mod persist;
+mod program;
+#[cfg(feature = "serde")]
+mod record;
+mod runtime;
+mod simulator;
+mod spawner;
+mod sub;
+mod timer;
+mod window;
+
+pub use app::{App, AppBuilder};
+pub use budget::{Budget, queue_depth};
+pub use cancel::{CancelToken, Cancelled};
+#[cfg(feature = "macros")]
+pub use chai_tea_macros::chai_app;
+pub use channel::{Channel, Delivery, Overflow};
+pub use cmd::{Cmd, no_run_cmd};
+pub use component::{Component, Dispatch, Embed};
+pub use error::Error;
+pub use fixed_step::{FixedStepBuilder, StepControl};
+pub use history::{History, Step};
+#[cfg(feature = "persistence")]
+pub use persist::Persistence;
+pub use program::{Program, brew_program, run_program};
+#[cfg(feature = "serde")]
+pub use record::{Divergence, Recorder, Replay};
+pub use simulator::Simulator;
+#[cfg(feature = "tokio")]
+pub use spawner::TokioSpawner;
+pub use spawner::{BoxFuture, InlineSpawner, Job, Spawner, ThreadPool, ThreadSpawner};
+pub use sub::Sub;
+pub use window::{WindowEvent, WindowProps};
+
+/// What code generated by [`chai_app`] refers to. Not part of the public API.
+#[doc(hidden)]
+#[cfg(feature = "macros")]
+pub mod __private {
+    pub use eframe;
 }
 
 /// Run a chai-tea app with a model, update, and view function.
 ///
 /// This is the minimal entry point. It wires up eframe and drives your Elm-style loop.
+///
+/// For an `update` that also returns a [`Cmd`], e.g. to retitle the window, build the app
+/// with [`App::builder`] and start it with [`AppBuilder::run_local`], or [`AppBuilder::run`]
+/// if the messages are `Send`.
 pub fn run<M, Msg, Finit, Fupdate, Fview>(
     title: &str,
     init: Finit,
@@ -215,7 +326,7 @@ pub fn run<M, Msg, Finit, Fupdate, Fview>(
     view: Fview,
 ) -> eframe::Result<()>
 where
-    M: Default + 'static,
+    M: 'static,
     Finit: Fn() -> M + 'static,
     Fupdate: Fn(M, Msg) -> M + Copy + 'static,
     Fview: Fn(&egui::Context, &M, &mut Vec<Msg>) + Copy + 'static,
@@ -236,24 +347,15 @@ pub fn run_with_opts<M, Msg, Finit, Fupdate, Fview>(
     view: Fview,
 ) -> eframe::Result<()>
 where
-    M: Default + 'static,
+    M: 'static,
     Finit: Fn() -> M + 'static,
     Fupdate: Fn(M, Msg) -> M + Copy + 'static,
     Fview: Fn(&egui::Context, &M, &mut Vec<Msg>) + Copy + 'static,
     Msg: 'static,
 {
-    eframe::run_native(
-        title,
-        options,
-        Box::new(move |_cc| {
-            Ok(Box::new(ChaiTeaApp {
-                model: init(),
-                messages: Vec::new(),
-                update,
-                view,
-            }))
-        }),
-    )
+    sync_builder(title, init, update, view)
+        .options(options)
+        .run_local()
 }
 
 /// An alias for [`run_with_opts`]. 🍵
@@ -266,7 +368,7 @@ pub fn brew_with_opts<M, Msg, Finit, Fupdate, Fview>(
     view: Fview,
 ) -> eframe::Result<()>
 where
-    M: Default + 'static,
+    M: 'static,
     Msg: 'static,
     Finit: Fn() -> M + 'static,
     Fupdate: Fn(M, Msg) -> M + Copy + 'static,
@@ -302,7 +404,7 @@ pub fn brew<M, Msg, Finit, Fupdate, Fview>(
     view: Fview,
 ) -> eframe::Result<()>
 where
-    M: Default + 'static,
+    M: 'static,
     Msg: 'static,
     Finit: Fn() -> M + 'static,
     Fupdate: Fn(M, Msg) -> M + Copy + 'static,
@@ -311,44 +413,63 @@ where
     run(title, init, update, view)
 }
 
-impl<M, Msg, Fupdate, Fview> eframe::App for ChaiTeaApp<M, Msg, Fupdate, Fview>
-where
-    M: Default + 'static,
-    Msg: 'static,
-    Fupdate: Fn(M, Msg) -> M + Copy + 'static,
-    Fview: Fn(&egui::Context, &M, &mut Vec<Msg>) + Copy + 'static,
-{
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        (self.view)(ctx, &self.model, &mut self.messages);
-        let msgs: Vec<_> = self.messages.drain(..).collect();
-        for msg in msgs {
-            let old = std::mem::take(&mut self.model);
-            self.model = (self.update)(old, msg);
-        }
-    }
-}
-
-struct ChaiTeaAppAsync<M, S, Cmd, Msg, Fupdate, Fview, Fcmd> {
-    model: M,
-    sync_state: S,
-    messages: Vec<Msg>,
-    update: Fupdate,
-    view: Fview,
-    run_cmd: Fcmd,
-    init_cmd: Vec<Cmd>,
-    chai_tx: ChaiSender<Msg>,
-    msg_rx: std::sync::mpsc::Receiver<Msg>,
-}
-
 /// A sender that automatically requests repaint on send.
+///
+/// It also carries the app's [`Spawner`], so `run_cmd` can start work with [`ChaiSender::spawn`].
+///
+/// The sender handed out for a keyed command (see [`Cmd::with_key`]) is tied to that command's
+/// [`CancelToken`]: once the command is cancelled or replaced, `send` fails, so stale results
+/// never reach `update` and worker loops that stop on a send error wind down on their own.
+///
+/// If the app's [`Channel`] is bounded, a full queue is handled by its [`Overflow`] policy;
+/// [`try_send`](ChaiSender::try_send) reports what the policy did.
 pub struct ChaiSender<T> {
-    tx: std::sync::mpsc::Sender<T>,
+    tx: channel::Transport<T>,
     ctx: Option<egui::Context>,
+    spawner: std::sync::Arc<dyn Spawner>,
+    token: Option<CancelToken>,
 }
 
 impl<T> ChaiSender<T> {
+    /// Wrap a plain `mpsc` sender.
+    ///
+    /// Messages go straight into `tx`: there is no app queue behind it, so no [`Channel`] bound
+    /// or [`Overflow`] policy applies, and no repaint is requested until [`set_ctx`](Self::set_ctx)
+    /// is called. Senders that feed an app come from the runtime, through `run_cmd`,
+    /// subscriptions or [`Simulator::sender`].
     pub fn new(tx: std::sync::mpsc::Sender<T>) -> Self {
-        Self { tx, ctx: None }
+        Self::from_transport(channel::Transport::Mpsc(tx))
+    }
+
+    pub(crate) fn from_transport(tx: channel::Transport<T>) -> Self {
+        Self {
+            tx,
+            ctx: None,
+            spawner: spawner::default_spawner(),
+            token: None,
+        }
+    }
+
+    pub(crate) fn with_spawner(self, spawner: std::sync::Arc<dyn Spawner>) -> Self {
+        Self { spawner, ..self }
+    }
+
+    pub(crate) fn with_token(self, token: CancelToken) -> Self {
+        Self {
+            token: Some(token),
+            ..self
+        }
+    }
+
+    /// The token of the keyed command this sender was handed to.
+    ///
+    /// For unkeyed commands this is a fresh token that is never cancelled.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.token.clone().unwrap_or_default()
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.token.as_ref().is_some_and(CancelToken::is_cancelled)
     }
 
     pub fn set_ctx(&mut self, ctx: &egui::Context) {
@@ -356,17 +477,80 @@ impl<T> ChaiSender<T> {
     }
 
     ///send `msg` and `request_repaint()`
+    ///
+    /// Fails only if the app is gone or the command was cancelled. A message a full bounded
+    /// [`Channel`] discards under [`Overflow::drop_newest`] still counts as sent, since dropping
+    /// it is the policy working; [`try_send`](ChaiSender::try_send) tells the two apart.
+    ///
+    /// ```
+    /// use chai_tea::{Channel, Overflow, Simulator};
+    ///
+    /// let channel = Channel::bounded(1, Overflow::drop_newest());
+    /// let mut sim = Simulator::new(|| (0, vec![]), || (), |n: i32, m: i32| (n + m, vec![]), |(), _, _| {})
+    ///     .with_channel(channel);
+    /// let tx = sim.sender();
+    ///
+    /// assert!(tx.send(1).is_ok());
+    /// assert!(tx.send(2).is_ok()); // discarded, the queue was full
+    /// sim.step();
+    /// assert_eq!(*sim.model(), 1);
+    /// ```
     pub fn send(&self, msg: T) -> Result<(), std::sync::mpsc::SendError<T>> {
+        if self.is_cancelled() {
+            return Err(std::sync::mpsc::SendError(msg));
+        }
         if let Some(ctx) = &self.ctx {
             ctx.request_repaint();
         }
-        self.tx.send(msg)
+        self.send_repaintless(msg)
     }
 
     ///send `msg` but don't `request_repaint()`
+    ///
+    /// Like [`send`](ChaiSender::send), a message [`Overflow::drop_newest`] discards counts as sent.
     #[inline(always)]
     pub fn send_repaintless(&self, msg: T) -> Result<(), std::sync::mpsc::SendError<T>> {
-        self.tx.send(msg)
+        if self.is_cancelled() {
+            return Err(std::sync::mpsc::SendError(msg));
+        }
+        match self.tx.send(msg, true) {
+            Ok(_) => Ok(()),
+            Err(
+                std::sync::mpsc::TrySendError::Full(msg)
+                | std::sync::mpsc::TrySendError::Disconnected(msg),
+            ) => Err(std::sync::mpsc::SendError(msg)),
+        }
+    }
+
+    /// Send `msg` without waiting, and `request_repaint()` if it was queued.
+    ///
+    /// On a full bounded [`Channel`], says which [`Overflow`] policy kicked in, handing back
+    /// whichever message it discarded. [`Overflow::block`] fails with `TrySendError::Full`
+    /// instead of waiting.
+    ///
+    /// ```
+    /// use chai_tea::{Channel, Delivery, Overflow, Simulator};
+    ///
+    /// let channel = Channel::bounded(2, Overflow::drop_oldest());
+    /// let sim = Simulator::new(|| (0, vec![]), || (), |n: i32, m: i32| (n + m, vec![]), |(), _, _| {})
+    ///     .with_channel(channel);
+    /// let tx = sim.sender();
+    ///
+    /// assert_eq!(tx.try_send(1), Ok(Delivery::Queued));
+    /// assert_eq!(tx.try_send(2), Ok(Delivery::Queued));
+    /// assert_eq!(tx.try_send(3), Ok(Delivery::DroppedOldest(1)));
+    /// ```
+    pub fn try_send(&self, msg: T) -> Result<Delivery<T>, std::sync::mpsc::TrySendError<T>> {
+        if self.is_cancelled() {
+            return Err(std::sync::mpsc::TrySendError::Disconnected(msg));
+        }
+        let delivery = self.tx.send(msg, false)?;
+        if !matches!(delivery, Delivery::DroppedNewest(_))
+            && let Some(ctx) = &self.ctx
+        {
+            ctx.request_repaint();
+        }
+        Ok(delivery)
     }
 
     pub fn with_ctx<F: FnOnce(&egui::Context)>(&self, f: F) {
@@ -374,12 +558,40 @@ impl<T> ChaiSender<T> {
             f(ctx);
         }
     }
-}
 
-impl<T> std::ops::Deref for ChaiSender<T> {
-    type Target = std::sync::mpsc::Sender<T>;
-    fn deref(&self) -> &Self::Target {
-        &self.tx
+    /// The plain `mpsc` sender, as senders were before they went through a [`Channel`].
+    ///
+    /// Messages sent through it skip the channel's bound and [`Overflow`] policy as well as the
+    /// repaint, and are only seen on the next frame something else triggers.
+    #[deprecated(note = "use `send` or `try_send`, which respect the app's `Channel`")]
+    pub fn mpsc(&self) -> &std::sync::mpsc::Sender<T> {
+        self.tx.mpsc()
+    }
+
+    /// Run `job` through the app's [`Spawner`], like the runtime does for [`Cmd`]s.
+    ///
+    /// ```
+    /// # enum Msg { Done(u64) }
+    /// fn run_cmd(cmd: (), _sync: &mut (), tx: chai_tea::ChaiSender<Msg>) {
+    ///     tx.clone().spawn(move || {
+    ///         let sum = (0..1_000).sum();
+    ///         tx.send(Msg::Done(sum)).ok();
+    ///     });
+    /// }
+    /// ```
+    pub fn spawn<F>(&self, job: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.spawner.spawn(Box::new(job));
+    }
+
+    /// Run `future` through the app's [`Spawner`].
+    pub fn spawn_future<F>(&self, future: F)
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        self.spawner.spawn_future(Box::pin(future));
     }
 }
 
@@ -388,18 +600,22 @@ impl<T> Clone for ChaiSender<T> {
         Self {
             tx: self.tx.clone(),
             ctx: self.ctx.clone(),
+            spawner: std::sync::Arc::clone(&self.spawner),
+            token: self.token.clone(),
         }
     }
 }
 
 /// An alias for [`run_async`]. 🍵
 ///
+/// Like it, this now needs `Send` messages; see [its breaking change](run_async#breaking-change).
+///
 /// # Example
 /// ```no_run
 /// # use eframe::egui;
-/// # fn init() -> i32 { 1 }
+/// # fn init() -> (i32, Vec<i32>) { (1, vec![]) }
 /// # fn sync_state_init() -> i32 { 1 }
-/// # fn update(m: i32, msg: i32) -> (i32, Option<i32>) { (1, None) }
+/// # fn update(m: i32, msg: i32) -> (i32, Vec<i32>) { (1, vec![]) }
 /// # fn view(ctx: &egui::Context, m: &i32, tx: &mut Vec<i32>) { }
 /// # fn run_cmd(cmd: i32, sync: &mut i32, tx: chai_tea::ChaiSender<i32>) { }
 /// chai_tea::brew_async("chai_app", init, sync_state_init, update, view, run_cmd);
@@ -408,15 +624,15 @@ impl<T> Clone for ChaiSender<T> {
 /// Equivalent to:
 /// ```no_run
 /// # use eframe::egui;
-/// # fn init() -> i32 { 1 }
+/// # fn init() -> (i32, Vec<i32>) { (1, vec![]) }
 /// # fn sync_state_init() -> i32 { 1 }
-/// # fn update(m: i32, msg: i32) -> (i32, Option<i32>) { (1, None) }
+/// # fn update(m: i32, msg: i32) -> (i32, Vec<i32>) { (1, vec![]) }
 /// # fn view(ctx: &egui::Context, m: &i32, tx: &mut Vec<i32>) { }
 /// # fn run_cmd(cmd: i32, sync: &mut i32, tx: chai_tea::ChaiSender<i32>) { }
 /// chai_tea::run_async("chai_app", init, sync_state_init, update, view, run_cmd);
 /// ```
 #[inline(always)]
-pub fn brew_async<M, S, Cmd, Msg, Finit, FsyncInit, Fupdate, Fview, Fcmd>(
+pub fn brew_async<M, S, C, Msg, Ri, R, Finit, FsyncInit, Fupdate, Fview, Fcmd>(
     title: &str,
     init: Finit,
     sync_state_init: FsyncInit,
@@ -425,15 +641,17 @@ pub fn brew_async<M, S, Cmd, Msg, Finit, FsyncInit, Fupdate, Fview, Fcmd>(
     run_cmd: Fcmd,
 ) -> eframe::Result<()>
 where
-    M: Default + 'static,
+    M: 'static,
     S: 'static,
-    Cmd: 'static,
-    Finit: Fn() -> (M, Vec<Cmd>) + 'static,
+    C: 'static,
+    Ri: Into<Cmd<Msg, C>>,
+    R: Into<Cmd<Msg, C>>,
+    Finit: Fn() -> (M, Ri) + 'static,
     FsyncInit: Fn() -> S + 'static,
-    Fupdate: Fn(M, Msg) -> (M, Vec<Cmd>) + Copy + 'static,
+    Fupdate: Fn(M, Msg) -> (M, R) + Copy + 'static,
     Fview: Fn(&egui::Context, &M, &mut Vec<Msg>) + Copy + 'static,
-    Fcmd: Fn(Cmd, &mut S, ChaiSender<Msg>) + Copy + Send + Sync + 'static,
-    Msg: 'static,
+    Fcmd: Fn(C, &mut S, ChaiSender<Msg>) + Copy + Send + Sync + 'static,
+    Msg: Send + 'static,
 {
     run_async(title, init, sync_state_init, update, view, run_cmd)
 }
@@ -441,7 +659,20 @@ where
 /// Run an async chai-tea app with a model, update, view, SyncState and async run_cmd function.
 ///
 /// This is the minimal entry point. It wires up eframe and drives your Elm-style loop.
-pub fn run_async<M, S, Cmd, Msg, Finit, FsyncInit, Fupdate, Fview, Fcmd>(
+///
+/// `init` and `update` may return a `Vec<C>` of your own commands, an `Option<C>`, or a
+/// composable [`Cmd`]. Apps with no command type of their own pass [`no_run_cmd`].
+///
+/// To set [`eframe::NativeOptions`] or combine features, use [`App::builder`]; this function is
+/// a shorthand for it.
+///
+/// # Breaking change
+///
+/// `Msg` has to be `Send`, where it used to only be `'static`: commands now hand their messages
+/// back from other threads. Apps whose messages can't cross threads build with
+/// [`App::builder`] and start with [`AppBuilder::run_local`] instead, which still runs
+/// `run_cmd` on the UI thread.
+pub fn run_async<M, S, C, Msg, Ri, R, Finit, FsyncInit, Fupdate, Fview, Fcmd>(
     title: &str,
     init: Finit,
     sync_state_init: FsyncInit,
@@ -450,84 +681,67 @@ pub fn run_async<M, S, Cmd, Msg, Finit, FsyncInit, Fupdate, Fview, Fcmd>(
     run_cmd: Fcmd,
 ) -> eframe::Result<()>
 where
-    M: Default + 'static,
+    M: 'static,
     S: 'static,
-    Cmd: 'static,
-    Finit: Fn() -> (M, Vec<Cmd>) + 'static,
+    C: 'static,
+    Ri: Into<Cmd<Msg, C>>,
+    R: Into<Cmd<Msg, C>>,
+    Finit: Fn() -> (M, Ri) + 'static,
     FsyncInit: Fn() -> S + 'static,
-    Fupdate: Fn(M, Msg) -> (M, Vec<Cmd>) + Copy + 'static,
+    Fupdate: Fn(M, Msg) -> (M, R) + Copy + 'static,
     Fview: Fn(&egui::Context, &M, &mut Vec<Msg>) + Copy + 'static,
-    Fcmd: Fn(Cmd, &mut S, ChaiSender<Msg>) + Copy + Send + Sync + 'static,
-    Msg: 'static,
+    Fcmd: Fn(C, &mut S, ChaiSender<Msg>) + Copy + Send + Sync + 'static,
+    Msg: Send + 'static,
 {
-    let options = eframe::NativeOptions::default();
-    let (msg_tx, msg_rx) = std::sync::mpsc::channel();
-
-    let chai_tx = ChaiSender::new(msg_tx);
-
-    let (model, init_cmd) = init();
+    async_builder(title, init, sync_state_init, update, view, run_cmd).run()
+}
 
-    eframe::run_native(
-        title,
-        options,
-        Box::new(move |_cc| {
-            Ok(Box::new(ChaiTeaAppAsync {
-                model,
-                sync_state: sync_state_init(),
-                messages: Vec::new(),
-                update,
-                view,
-                run_cmd,
-                init_cmd,
-                chai_tx,
-                msg_rx,
-            }))
-        }),
-    )
+/// The builder behind the sync entry points, whose `update` returns no commands.
+fn sync_builder<M, Msg, Finit, Fupdate, Fview>(
+    title: &str,
+    init: Finit,
+    update: Fupdate,
+    view: Fview,
+) -> AppBuilder<M, Msg>
+where
+    M: 'static,
+    Msg: 'static,
+    Finit: Fn() -> M + 'static,
+    Fupdate: Fn(M, Msg) -> M + 'static,
+    Fview: Fn(&egui::Context, &M, &mut Vec<Msg>) + 'static,
+{
+    App::builder(title)
+        .init(move || (init(), Cmd::none()))
+        .update(move |model, msg| (update(model, msg), Cmd::none()))
+        .view(view)
 }
 
-impl<M, S, Cmd, Msg, Fupdate, Fview, Fcmd> eframe::App
-    for ChaiTeaAppAsync<M, S, Cmd, Msg, Fupdate, Fview, Fcmd>
+/// The builder behind the async entry points.
+fn async_builder<M, S, C, Msg, Ri, R, Finit, FsyncInit, Fupdate, Fview, Fcmd>(
+    title: &str,
+    init: Finit,
+    sync_state_init: FsyncInit,
+    update: Fupdate,
+    view: Fview,
+    run_cmd: Fcmd,
+) -> AppBuilder<M, Msg, S, C>
 where
-    M: Default + 'static,
+    M: 'static,
     S: 'static,
-    Cmd: 'static,
+    C: 'static,
     Msg: 'static,
-    Fupdate: Fn(M, Msg) -> (M, Vec<Cmd>) + Copy + 'static,
-    Fview: Fn(&egui::Context, &M, &mut Vec<Msg>) + Copy + 'static,
-    Fcmd: Fn(Cmd, &mut S, ChaiSender<Msg>) + Copy + Send + Sync + 'static,
+    Ri: Into<Cmd<Msg, C>>,
+    R: Into<Cmd<Msg, C>>,
+    Finit: Fn() -> (M, Ri) + 'static,
+    FsyncInit: Fn() -> S + 'static,
+    Fupdate: Fn(M, Msg) -> (M, R) + 'static,
+    Fview: Fn(&egui::Context, &M, &mut Vec<Msg>) + 'static,
+    Fcmd: Fn(C, &mut S, ChaiSender<Msg>) + 'static,
 {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        static ONCE: std::sync::Once = std::sync::Once::new();
-
-        let mut cmds = Vec::<Cmd>::new();
-
-        ONCE.call_once(|| {
-            self.chai_tx.set_ctx(ctx);
-            cmds = std::mem::take(&mut self.init_cmd);
-        });
-
-        //get view messages
-        (self.view)(ctx, &self.model, &mut self.messages);
-        let mut msgs: Vec<_> = self.messages.drain(..).collect();
-
-        //get async messages
-        while let Ok(msg) = self.msg_rx.try_recv() {
-            msgs.push(msg);
-        }
-
-        //handle them all
-        for msg in msgs {
-            let old = std::mem::take(&mut self.model);
-            let (new_model, mut new_cmds) = (self.update)(old, msg);
-            self.model = new_model;
-            cmds.append(&mut new_cmds);
-        }
-
-        //run async cmds
-        for cmd in cmds {
-            let tx = ChaiSender::clone(&self.chai_tx);
-            (self.run_cmd)(cmd, &mut self.sync_state, tx);
-        }
-    }
+    App::builder(title)
+        .sync_state(sync_state_init)
+        .init(init)
+        .update(update)
+        .view(view)
+        .run_cmd(run_cmd)
 }
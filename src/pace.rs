@@ -0,0 +1,210 @@
+//! Debouncing and throttling messages from `view` before `update` sees them.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::cancel::key_of;
+use crate::timer::Timers;
+
+type KeyFn<Msg> = Box<dyn Fn(&Msg) -> Option<u64>>;
+
+enum Pace {
+    Debounce(Duration),
+    Throttle(Duration),
+}
+
+struct Rule<Msg> {
+    pace: Pace,
+    key: KeyFn<Msg>,
+    /// Throttling only: when each key last went, or will go, through to `update`.
+    last: HashMap<u64, Instant>,
+}
+
+/// The debounce and throttle rules an app declared, applied in the order they were added.
+pub(crate) struct Pacer<Msg> {
+    rules: Vec<Rule<Msg>>,
+}
+
+impl<Msg> Pacer<Msg> {
+    pub(crate) fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    pub(crate) fn debounce<K, F>(&mut self, quiet: Duration, key: F)
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        self.add(Pace::Debounce(quiet), key);
+    }
+
+    pub(crate) fn throttle<K, F>(&mut self, interval: Duration, key: F)
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        self.add(Pace::Throttle(interval), key);
+    }
+
+    fn add<K, F>(&mut self, pace: Pace, key: F)
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        // keep the timers' keys apart from `Cmd::with_key` ones and from other rules
+        let rule = self.rules.len();
+        self.rules.push(Rule {
+            pace,
+            key: Box::new(move |msg| key(msg).map(|key| key_of(("chai_tea::pace", rule, key)))),
+            last: HashMap::new(),
+        });
+    }
+
+    /// Let through the messages that may reach `update` now, scheduling the rest on `timers`.
+    pub(crate) fn pace(&mut self, msgs: Vec<Msg>, timers: &mut Timers<Msg>) -> Vec<Msg> {
+        self.pace_at(msgs, timers, Instant::now())
+    }
+
+    /// [`pace`](Pacer::pace), as of `now`.
+    fn pace_at(&mut self, msgs: Vec<Msg>, timers: &mut Timers<Msg>, now: Instant) -> Vec<Msg> {
+        if self.rules.is_empty() {
+            return msgs;
+        }
+
+        let mut ready = Vec::with_capacity(msgs.len());
+        'msgs: for msg in msgs {
+            for rule in &mut self.rules {
+                let Some(key) = (rule.key)(&msg) else {
+                    continue;
+                };
+                match rule.pace {
+                    Pace::Debounce(quiet) => {
+                        timers.cancel(key);
+                        timers.schedule(now + quiet, Some(key), msg);
+                    }
+                    Pace::Throttle(interval) => match rule.last.get(&key).copied() {
+                        // a trailing message is already waiting, take its place
+                        Some(last) if last > now => {
+                            timers.cancel(key);
+                            timers.schedule(last, Some(key), msg);
+                        }
+                        // went through too recently, trail behind it
+                        Some(last) if now < last + interval => {
+                            timers.schedule(last + interval, Some(key), msg);
+                            rule.last.insert(key, last + interval);
+                        }
+                        _ => {
+                            rule.last.insert(key, now);
+                            ready.push(msg);
+                        }
+                    },
+                }
+                continue 'msgs;
+            }
+            ready.push(msg);
+        }
+
+        for rule in &mut self.rules {
+            if let Pace::Throttle(interval) = rule.pace {
+                rule.last.retain(|_, last| *last + interval > now);
+            }
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    /// Messages keyed by their letter; `'-'` is left unpaced.
+    fn by_letter(msg: &(char, u32)) -> Option<char> {
+        (msg.0 != '-').then_some(msg.0)
+    }
+
+    #[test]
+    fn debounce_delivers_only_the_last_after_a_quiet_spell() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new();
+        pacer.debounce(ms(20), by_letter);
+        let mut timers = Timers::new();
+
+        for (i, at) in [0, 5, 10].into_iter().enumerate() {
+            let ready = pacer.pace_at(vec![('a', i as u32)], &mut timers, start + ms(at));
+            assert!(ready.is_empty());
+        }
+        assert!(timers.pop_due(start + ms(29)).is_empty());
+        assert_eq!(timers.pop_due(start + ms(30)), [('a', 2)]);
+    }
+
+    #[test]
+    fn debounce_keeps_keys_apart_and_lets_unkeyed_through() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new();
+        pacer.debounce(ms(20), by_letter);
+        let mut timers = Timers::new();
+
+        let msgs = vec![('a', 1), ('b', 2), ('-', 3), ('a', 4)];
+        assert_eq!(pacer.pace_at(msgs, &mut timers, start), [('-', 3)]);
+        assert_eq!(timers.pop_due(start + ms(20)), [('b', 2), ('a', 4)]);
+    }
+
+    #[test]
+    fn throttle_lets_the_first_through_and_trails_the_last() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new();
+        pacer.throttle(ms(100), by_letter);
+        let mut timers = Timers::new();
+
+        // leading edge
+        assert_eq!(
+            pacer.pace_at(vec![('a', 1)], &mut timers, start),
+            [('a', 1)]
+        );
+        // within the interval: the newest waits for its end, replacing any that waited before it
+        assert!(
+            pacer
+                .pace_at(vec![('a', 2)], &mut timers, start + ms(10))
+                .is_empty()
+        );
+        assert!(
+            pacer
+                .pace_at(vec![('a', 3)], &mut timers, start + ms(20))
+                .is_empty()
+        );
+        assert!(timers.pop_due(start + ms(99)).is_empty());
+        assert_eq!(timers.pop_due(start + ms(100)), [('a', 3)]);
+
+        // the trailing message started a new interval
+        assert!(
+            pacer
+                .pace_at(vec![('a', 4)], &mut timers, start + ms(150))
+                .is_empty()
+        );
+        assert_eq!(timers.pop_due(start + ms(200)), [('a', 4)]);
+
+        // once an interval passes quietly, the next goes straight through again
+        let ready = pacer.pace_at(vec![('a', 5)], &mut timers, start + ms(400));
+        assert_eq!(ready, [('a', 5)]);
+    }
+
+    #[test]
+    fn throttle_keeps_keys_apart() {
+        let start = Instant::now();
+        let mut pacer = Pacer::new();
+        pacer.throttle(ms(100), by_letter);
+        let mut timers = Timers::new();
+
+        let msgs = vec![('a', 1), ('b', 2), ('a', 3), ('-', 4)];
+        assert_eq!(
+            pacer.pace_at(msgs, &mut timers, start),
+            [('a', 1), ('b', 2), ('-', 4)]
+        );
+        assert_eq!(timers.pop_due(start + ms(100)), [('a', 3)]);
+    }
+}
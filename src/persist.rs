@@ -0,0 +1,332 @@
+//! Saving the model through eframe's storage and restoring it on the next launch.
+
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+type SaveFn<M> = Box<dyn Fn(&M) -> serde_json::Result<serde_json::Value>>;
+type RestoreFn<M, P> = Box<dyn Fn(&mut M, P)>;
+type MigrateFn<P> = Box<dyn Fn(serde_json::Value) -> serde_json::Result<P>>;
+
+/// What gets written to storage: the data and the schema version it was saved with.
+#[derive(Serialize, Deserialize)]
+struct Saved<T> {
+    version: u32,
+    data: T,
+}
+
+/// Which part of the model survives restarts, and how to bring old saves up to date.
+///
+/// Requires the `persistence` feature. The persisted data `P` is written through
+/// [`eframe::App::save`], which eframe calls on its autosave interval and on exit, and
+/// restored over the model `init` returned when the app next starts.
+///
+/// Saves are tagged with a schema [`version`](Persistence::version). When the version found in
+/// storage doesn't match, the [`migrate`](Persistence::migrate) registered for it converts the
+/// old data; if there is none, the save is left unused and an [`Error`] goes to the
+/// [`on_error`](crate::AppBuilder::on_error) hook.
+///
+/// # Example
+/// ```no_run
+/// # use eframe::egui;
+/// # use serde::{Deserialize, Serialize};
+/// use chai_tea::Persistence;
+///
+/// #[derive(Default)]
+/// struct Model { notes: Vec<String>, draft: String }
+/// # enum Msg {}
+/// # fn update(m: Model, msg: Msg) -> (Model, chai_tea::Cmd<Msg>) { (m, chai_tea::Cmd::none()) }
+/// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+///
+/// // version 1 stored the notes as one string
+/// #[derive(Deserialize)]
+/// struct NotesV1 { text: String }
+///
+/// let persistence = Persistence::slice(
+///     |m: &Model| m.notes.clone(),
+///     |m: &mut Model, notes| m.notes = notes,
+/// )
+/// .version(2)
+/// .migrate(1, |old: NotesV1| old.text.lines().map(String::from).collect());
+///
+/// chai_tea::App::builder("notes")
+///     .init(|| (Model::default(), chai_tea::Cmd::none()))
+///     .update(update)
+///     .view(view)
+///     .persistence(persistence)
+///     .run();
+/// ```
+pub struct Persistence<M, P> {
+    key: String,
+    version: u32,
+    save: SaveFn<M>,
+    restore: RestoreFn<M, P>,
+    migrations: HashMap<u32, MigrateFn<P>>,
+}
+
+impl<M> Persistence<M, M>
+where
+    M: Serialize + DeserializeOwned + 'static,
+{
+    /// Persist the whole model.
+    pub fn model() -> Self {
+        Self {
+            key: String::from("chai_tea_model"),
+            version: 0,
+            save: Box::new(|model| serde_json::to_value(model)),
+            restore: Box::new(|model, saved| *model = saved),
+            migrations: HashMap::new(),
+        }
+    }
+}
+
+impl<M, P> Persistence<M, P>
+where
+    P: Serialize + DeserializeOwned + 'static,
+{
+    /// Persist the part of the model `get` picks out, putting it back with `set`.
+    pub fn slice<Fget, Fset>(get: Fget, set: Fset) -> Self
+    where
+        Fget: Fn(&M) -> P + 'static,
+        Fset: Fn(&mut M, P) + 'static,
+    {
+        Self {
+            key: String::from("chai_tea_model"),
+            version: 0,
+            save: Box::new(move |model| serde_json::to_value(get(model))),
+            restore: Box::new(set),
+            migrations: HashMap::new(),
+        }
+    }
+}
+
+impl<M, P> Persistence<M, P> {
+    /// The storage key to save under. Defaults to `"chai_tea_model"`.
+    pub fn key(self, key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            ..self
+        }
+    }
+
+    /// The schema version saves are tagged with. Defaults to `0`.
+    pub fn version(self, version: u32) -> Self {
+        Self { version, ..self }
+    }
+
+    /// Convert data saved under schema version `from`, read as `Old`, into the current format.
+    ///
+    /// Register one per old version you still want to load. Migrations don't chain: a version 1
+    /// save goes through the migration registered for `1` straight to the current format, never
+    /// on through the one for `2`, so each has to convert all the way.
+    pub fn migrate<Old, F>(mut self, from: u32, migrate: F) -> Self
+    where
+        Old: DeserializeOwned,
+        F: Fn(Old) -> P + 'static,
+    {
+        self.migrations.insert(
+            from,
+            Box::new(move |data| serde_json::from_value(data).map(&migrate)),
+        );
+        self
+    }
+}
+
+/// A [`Persistence`] with its data type erased, as the apps hold it.
+pub(crate) trait Store<M> {
+    /// Put saved data, migrated if needed, back into `model`. On error `model` is left as it was.
+    fn restore(&self, model: &mut M, storage: Option<&dyn eframe::Storage>) -> Result<(), Error>;
+
+    fn save(&self, model: &M, storage: &mut dyn eframe::Storage) -> Result<(), Error>;
+}
+
+impl<M, P> Store<M> for Persistence<M, P>
+where
+    P: DeserializeOwned,
+{
+    fn restore(&self, model: &mut M, storage: Option<&dyn eframe::Storage>) -> Result<(), Error> {
+        let Some(text) = storage.and_then(|storage| storage.get_string(&self.key)) else {
+            return Ok(());
+        };
+        let saved: Saved<serde_json::Value> =
+            serde_json::from_str(&text).map_err(|source| Error::UnreadableSave {
+                key: self.key.clone(),
+                source,
+            })?;
+
+        let data = if saved.version == self.version {
+            serde_json::from_value(saved.data)
+        } else if let Some(migrate) = self.migrations.get(&saved.version) {
+            migrate(saved.data)
+        } else {
+            return Err(Error::NoMigration {
+                key: self.key.clone(),
+                from: saved.version,
+                to: self.version,
+            });
+        };
+
+        let data = data.map_err(|source| Error::BadSave {
+            key: self.key.clone(),
+            version: saved.version,
+            source,
+        })?;
+        (self.restore)(model, data);
+        Ok(())
+    }
+
+    fn save(&self, model: &M, storage: &mut dyn eframe::Storage) -> Result<(), Error> {
+        let text = (self.save)(model)
+            .and_then(|data| {
+                serde_json::to_string(&Saved {
+                    version: self.version,
+                    data,
+                })
+            })
+            .map_err(|source| Error::Save {
+                key: self.key.clone(),
+                source,
+            })?;
+        storage.set_string(&self.key, text);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Memory(HashMap<String, String>);
+
+    impl eframe::Storage for Memory {
+        fn get_string(&self, key: &str) -> Option<String> {
+            self.0.get(key).cloned()
+        }
+
+        fn set_string(&mut self, key: &str, value: String) {
+            self.0.insert(key.to_owned(), value);
+        }
+
+        fn flush(&mut self) {}
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Model {
+        notes: Vec<String>,
+        draft: String,
+    }
+
+    fn model() -> Model {
+        Model {
+            notes: vec!["tea".into(), "biscuits".into()],
+            draft: "milk".into(),
+        }
+    }
+
+    fn notes() -> Persistence<Model, Vec<String>> {
+        Persistence::slice(|m: &Model| m.notes.clone(), |m, notes| m.notes = notes)
+    }
+
+    fn stored(key: &str, text: &str) -> Memory {
+        Memory(HashMap::from([(key.to_owned(), text.to_owned())]))
+    }
+
+    #[test]
+    fn the_model_round_trips() {
+        let persistence = Persistence::model().key("app").version(3);
+        let mut storage = Memory::default();
+        persistence.save(&model(), &mut storage).unwrap();
+
+        let mut restored = Model::default();
+        persistence.restore(&mut restored, Some(&storage)).unwrap();
+
+        assert_eq!(restored, model());
+        assert!(storage.0["app"].starts_with(r#"{"version":3,"#));
+    }
+
+    #[test]
+    fn a_slice_only_saves_and_restores_its_part() {
+        let persistence = notes();
+        let mut storage = Memory::default();
+        persistence.save(&model(), &mut storage).unwrap();
+
+        let mut restored = Model {
+            draft: "fresh".into(),
+            ..Model::default()
+        };
+        persistence.restore(&mut restored, Some(&storage)).unwrap();
+
+        assert_eq!(restored.notes, model().notes);
+        assert_eq!(restored.draft, "fresh");
+    }
+
+    #[test]
+    fn nothing_saved_leaves_the_model_alone() {
+        let mut restored = model();
+
+        notes()
+            .restore(&mut restored, Some(&Memory::default()))
+            .unwrap();
+        notes().restore(&mut restored, None).unwrap();
+
+        assert_eq!(restored, model());
+    }
+
+    #[test]
+    fn an_old_version_without_a_migration_is_ignored() {
+        let storage = stored("chai_tea_model", r#"{"version":1,"data":["tea"]}"#);
+        let mut restored = model();
+
+        let err = notes().version(2).restore(&mut restored, Some(&storage));
+
+        assert!(matches!(
+            err,
+            Err(Error::NoMigration { from: 1, to: 2, .. })
+        ));
+        assert_eq!(restored, model());
+    }
+
+    #[test]
+    fn an_old_version_is_migrated_in_one_hop() {
+        #[derive(Deserialize)]
+        struct NotesV1 {
+            text: String,
+        }
+        let persistence = notes()
+            .version(3)
+            .migrate(1, |old: NotesV1| {
+                old.text.lines().map(String::from).collect()
+            })
+            .migrate(2, |_: Vec<String>| -> Vec<String> {
+                panic!("a version 1 save went through the version 2 migration")
+            });
+        let storage = stored(
+            "chai_tea_model",
+            r#"{"version":1,"data":{"text":"tea\nscones"}}"#,
+        );
+        let mut restored = Model::default();
+
+        persistence.restore(&mut restored, Some(&storage)).unwrap();
+
+        assert_eq!(restored.notes, ["tea", "scones"]);
+    }
+
+    #[test]
+    fn a_corrupt_save_is_reported_and_ignored() {
+        let mut restored = model();
+
+        let garbage = stored("chai_tea_model", "not json");
+        let err = notes().restore(&mut restored, Some(&garbage));
+        assert!(matches!(err, Err(Error::UnreadableSave { .. })));
+
+        let wrong_shape = stored("chai_tea_model", r#"{"version":0,"data":{"notes":7}}"#);
+        let err = notes().restore(&mut restored, Some(&wrong_shape));
+        assert!(matches!(err, Err(Error::BadSave { version: 0, .. })));
+
+        assert_eq!(restored, model());
+    }
+}
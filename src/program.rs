@@ -0,0 +1,175 @@
+//! A whole app as one trait implementation, instead of loose functions.
+
+use eframe::egui;
+
+#[cfg(feature = "persistence")]
+use crate::persist::Store;
+use crate::{ChaiSender, Cmd, Sub};
+
+/// An app's types and functions, gathered on one type.
+///
+/// Implementing `Program` does what passing each function to [`run_async`](crate::run_async)
+/// does, but the types are named once, and compiler errors point at the method that's wrong
+/// rather than at a call with nine inferred type parameters. Optional parts have defaults that do
+/// nothing.
+///
+/// Both are shorthands for [`App::builder`](crate::App::builder), side by side: the loose
+/// functions can be closures, which a trait of associated functions has nowhere to keep, so
+/// `run_async` isn't built on `Program`.
+///
+/// Run it with [`run_program`], or start from [`App::program`](crate::App::program) to add more
+/// features.
+///
+/// # Example
+/// ```no_run
+/// use std::convert::Infallible;
+/// use eframe::egui;
+/// use chai_tea::{ChaiSender, Cmd, Program};
+///
+/// struct Counter;
+///
+/// enum Msg { Inc, Dec }
+///
+/// impl Program for Counter {
+///     const TITLE: &'static str = "counter";
+///
+///     type Model = i32;
+///     type Msg = Msg;
+///     type Cmd = Infallible;
+///     type Sync = ();
+///
+///     fn init() -> (i32, Cmd<Msg>) {
+///         (0, Cmd::none())
+///     }
+///
+///     fn sync_state_init() {}
+///
+///     fn update(count: i32, msg: Msg) -> (i32, Cmd<Msg>) {
+///         match msg {
+///             Msg::Inc => (count + 1, Cmd::none()),
+///             Msg::Dec => (count - 1, Cmd::none()),
+///         }
+///     }
+///
+///     fn view(ctx: &egui::Context, count: &i32, tx: &mut Vec<Msg>) {
+///         egui::CentralPanel::default().show(ctx, |ui| {
+///             if ui.button("+").clicked() { tx.push(Msg::Inc); }
+///             if ui.button("–").clicked() { tx.push(Msg::Dec); }
+///             ui.label(count.to_string());
+///         });
+///     }
+///
+///     fn run_cmd(cmd: Infallible, _sync: &mut (), _tx: ChaiSender<Msg>) {
+///         match cmd {}
+///     }
+/// }
+///
+/// fn main() -> eframe::Result<()> {
+///     chai_tea::run_program::<Counter>()
+/// }
+/// ```
+pub trait Program: 'static {
+    /// The window title. eframe also uses it to name the app's storage.
+    const TITLE: &'static str;
+
+    type Model: 'static;
+    type Msg: Send + 'static;
+    /// The app's own command type, handled by [`run_cmd`](Program::run_cmd). Use
+    /// [`Infallible`](std::convert::Infallible) if every command is a [`Cmd`].
+    type Cmd: 'static;
+    /// The `SyncState` handed to [`run_cmd`](Program::run_cmd).
+    type Sync: 'static;
+
+    fn init() -> (Self::Model, Cmd<Self::Msg, Self::Cmd>);
+
+    fn sync_state_init() -> Self::Sync;
+
+    /// How a message changes the model. Implement either this or
+    /// [`update_mut`](Program::update_mut).
+    fn update(model: Self::Model, msg: Self::Msg) -> (Self::Model, Cmd<Self::Msg, Self::Cmd>) {
+        let mut model = model;
+        let cmd = Self::update_mut(&mut model, msg);
+        (model, cmd)
+    }
+
+    /// Like [`update`](Program::update), changing the model in place, as
+    /// [`AppBuilder::update_mut`](crate::AppBuilder::update_mut) does.
+    ///
+    /// # Panics
+    /// If neither this nor `update` is implemented.
+    fn update_mut(_model: &mut Self::Model, _msg: Self::Msg) -> Cmd<Self::Msg, Self::Cmd> {
+        unimplemented!(
+            "{} implements neither Program::update nor update_mut",
+            Self::TITLE
+        )
+    }
+
+    fn view(ctx: &egui::Context, model: &Self::Model, tx: &mut Vec<Self::Msg>);
+
+    fn run_cmd(cmd: Self::Cmd, sync_state: &mut Self::Sync, tx: ChaiSender<Self::Msg>);
+
+    /// Options for the window. Defaults to eframe's.
+    fn options() -> eframe::NativeOptions {
+        eframe::NativeOptions::default()
+    }
+
+    /// The [`Sub`]scriptions the model wants running. Defaults to none.
+    fn subscriptions(_model: &Self::Model) -> Sub<Self::Msg> {
+        Sub::none()
+    }
+
+    /// Put what [`save`](Program::save) wrote back into the model `init` returned.
+    ///
+    /// Requires the `persistence` feature. Called once, before the first frame.
+    #[cfg(feature = "persistence")]
+    fn restore(_model: &mut Self::Model, _storage: &dyn eframe::Storage) {}
+
+    /// Write what should survive a restart to `storage`.
+    ///
+    /// Requires the `persistence` feature. eframe calls it on its autosave interval and on exit.
+    #[cfg(feature = "persistence")]
+    fn save(_model: &Self::Model, _storage: &mut dyn eframe::Storage) {}
+
+    /// Called once when the window closes, after the last [`save`](Program::save).
+    fn on_exit(_model: &Self::Model, _sync_state: &mut Self::Sync) {}
+}
+
+/// An alias for [`run_program`]. 🍵
+#[inline(always)]
+pub fn brew_program<P: Program>() -> eframe::Result<()> {
+    run_program::<P>()
+}
+
+/// Run a [`Program`] until its window is closed.
+///
+/// Shorthand for `App::program::<P>().run()`.
+pub fn run_program<P: Program>() -> eframe::Result<()> {
+    crate::App::program::<P>().run()
+}
+
+/// A [`Program`]'s `restore` and `save`, as the runtime holds persistence.
+#[cfg(feature = "persistence")]
+pub(crate) struct ProgramStore<P>(pub(crate) std::marker::PhantomData<P>);
+
+#[cfg(feature = "persistence")]
+impl<P: Program> Store<P::Model> for ProgramStore<P> {
+    fn restore(
+        &self,
+        model: &mut P::Model,
+        storage: Option<&dyn eframe::Storage>,
+    ) -> Result<(), crate::Error> {
+        if let Some(storage) = storage {
+            P::restore(model, storage);
+        }
+        Ok(())
+    }
+
+    fn save(
+        &self,
+        model: &P::Model,
+        storage: &mut dyn eframe::Storage,
+    ) -> Result<(), crate::Error> {
+        P::save(model, storage);
+        Ok(())
+    }
+}
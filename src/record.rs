@@ -0,0 +1,472 @@
+//! Recording processed messages to a JSON-lines log, and replaying them.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+use crate::cancel::key_of;
+
+/// One line of a message log.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Line<Msg> {
+    /// The runtime frame the message was processed in.
+    pub(crate) frame: u64,
+    /// Milliseconds since recording started.
+    pub(crate) at_ms: u64,
+    pub(crate) msg: Msg,
+    /// Hash of the model `update` returned, if the recorder was hashing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) hash: Option<u64>,
+}
+
+/// Writes every message `update` processes, view and async alike, to a JSON-lines log.
+///
+/// Each line holds the frame number, the milliseconds since recording started, and the message.
+/// With [`with_model_hash`](Recorder::with_model_hash) it also holds a hash of the resulting
+/// model, which lets a [`Replay`] point at the first message where it diverges.
+///
+/// The log is flushed after every frame, so it survives a crash. If writing fails, recording
+/// stops and an [`Error`] goes to the [`on_error`](crate::AppBuilder::on_error) hook.
+///
+/// # Example
+/// ```no_run
+/// # use eframe::egui;
+/// # fn update(m: Model, msg: Msg) -> (Model, Vec<()>) { (m, vec![]) }
+/// # fn view(ctx: &egui::Context, m: &Model, tx: &mut Vec<Msg>) { }
+/// #[derive(Default, Hash)]
+/// struct Model { counter: i32 }
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// enum Msg { Inc, Dec }
+///
+/// let recorder = chai_tea::Recorder::create("session.jsonl")?.with_model_hash();
+/// chai_tea::App::builder("chai_app")
+///     .init(|| (Model::default(), vec![]))
+///     .update(update)
+///     .view(view)
+///     .recorder(recorder)
+///     .run();
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Recorder<M, Msg> {
+    out: Box<dyn Write>,
+    started: Instant,
+    encode: fn(&Msg) -> serde_json::Result<serde_json::Value>,
+    hash: Option<fn(&M) -> u64>,
+    failed: bool,
+}
+
+impl<M, Msg: Serialize> Recorder<M, Msg> {
+    /// Record to a new file at `path`, truncating any existing one.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Ok(Self::from_writer(BufWriter::new(file)))
+    }
+
+    /// Record to any writer.
+    pub fn from_writer(out: impl Write + 'static) -> Self {
+        Self {
+            out: Box::new(out),
+            started: Instant::now(),
+            encode: |msg| serde_json::to_value(msg),
+            hash: None,
+            failed: false,
+        }
+    }
+}
+
+impl<M, Msg> Recorder<M, Msg> {
+    /// Also record a hash of the model after each message.
+    pub fn with_model_hash(self) -> Self
+    where
+        M: Hash,
+    {
+        Self {
+            hash: Some(|model| key_of(model)),
+            ..self
+        }
+    }
+
+    /// Serialize `msg` before `update` consumes it. `None` once recording has stopped.
+    pub(crate) fn encode(&self, msg: &Msg) -> Result<Option<serde_json::Value>, Error> {
+        if self.failed {
+            return Ok(None);
+        }
+        (self.encode)(msg)
+            .map(Some)
+            .map_err(|source| Error::UnrecordableMessage { source })
+    }
+
+    /// Write the line for an encoded message, given the model `update` returned for it.
+    pub(crate) fn write(
+        &mut self,
+        frame: u64,
+        msg: serde_json::Value,
+        model: &M,
+    ) -> Result<(), Error> {
+        let line = Line {
+            frame,
+            at_ms: self.started.elapsed().as_millis() as u64,
+            msg,
+            hash: self.hash.map(|hash| hash(model)),
+        };
+        let result = serde_json::to_writer(&mut self.out, &line)
+            .map_err(io::Error::from)
+            .and_then(|()| self.out.write_all(b"\n"));
+        self.fail_on(result)
+    }
+
+    /// Flush what this frame wrote.
+    pub(crate) fn flush(&mut self) -> Result<(), Error> {
+        if self.failed {
+            return Ok(());
+        }
+        let result = self.out.flush();
+        self.fail_on(result)
+    }
+
+    fn fail_on(&mut self, result: io::Result<()>) -> Result<(), Error> {
+        result.map_err(|source| {
+            self.failed = true;
+            Error::RecordingStopped { source }
+        })
+    }
+}
+
+/// A message log written by a [`Recorder`], to feed back through `init` and `update`.
+///
+/// Replay it headlessly with [`Simulator::replay`](crate::Simulator::replay), or in the window
+/// with [`AppBuilder::replay`](crate::AppBuilder::replay). Either way commands and subscriptions are
+/// not run: their messages are already in the log, so the model goes through exactly the
+/// recorded sequence of updates.
+///
+/// # Example
+/// ```
+/// use chai_tea::{Recorder, Replay, Simulator};
+///
+/// #[derive(Default, Hash)]
+/// struct Model { counter: i32 }
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// enum Msg { Inc }
+///
+/// fn update(m: Model, msg: Msg) -> (Model, Vec<()>) {
+///     match msg {
+///         Msg::Inc => (Model { counter: m.counter + 1 }, vec![]),
+///     }
+/// }
+/// fn run_cmd(_: (), _: &mut (), _: chai_tea::ChaiSender<Msg>) {}
+///
+/// let path = std::env::temp_dir().join("chai-tea-replay-doctest.jsonl");
+/// let mut sim = Simulator::new(|| (Model::default(), vec![]), || (), update, run_cmd)
+///     .with_recorder(Recorder::create(&path)?.with_model_hash());
+/// sim.dispatch(Msg::Inc);
+/// sim.dispatch(Msg::Inc);
+/// sim.step();
+/// drop(sim);
+///
+/// let mut replayed = Simulator::new(|| (Model::default(), vec![]), || (), update, run_cmd);
+/// replayed.replay(Replay::open(&path)?.with_model_hash()).unwrap();
+/// assert_eq!(replayed.model().counter, 2);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct Replay<M, Msg> {
+    lines: Vec<Line<Msg>>,
+    hash: Option<fn(&M) -> u64>,
+}
+
+impl<M, Msg: DeserializeOwned> Replay<M, Msg> {
+    /// Read the log at `path`.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Read a log from any buffered reader. Blank lines are skipped.
+    pub fn from_reader(reader: impl BufRead) -> io::Result<Self> {
+        let mut lines = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                lines.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Self { lines, hash: None })
+    }
+}
+
+impl<M, Msg> Replay<M, Msg> {
+    /// Check the model against the hashes in the log, reporting the first mismatch as a [`Divergence`].
+    pub fn with_model_hash(self) -> Self
+    where
+        M: Hash,
+    {
+        Self {
+            hash: Some(|model| key_of(model)),
+            ..self
+        }
+    }
+
+    /// How many messages the log holds.
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Whether the log holds no messages at all.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+/// The first message after which the replayed model no longer matched the recording.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    /// Position of the message in the log.
+    pub index: usize,
+    /// The frame it was recorded in.
+    pub frame: u64,
+    /// The model hash that was recorded.
+    pub expected: u64,
+    /// The model hash the replay produced.
+    pub actual: u64,
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "replay diverged at message {} (frame {}): model hash {:#x}, recorded {:#x}",
+            self.index, self.frame, self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Feeds a [`Replay`] back in, one line at a time, and keeps track of how it went.
+pub(crate) struct Player<M, Msg> {
+    lines: VecDeque<Line<Msg>>,
+    total: usize,
+    hash: Option<fn(&M) -> u64>,
+    started: Option<Instant>,
+    pub(crate) divergence: Option<Divergence>,
+}
+
+impl<M, Msg> Player<M, Msg> {
+    pub(crate) fn new(replay: Replay<M, Msg>) -> Self {
+        Self {
+            total: replay.lines.len(),
+            lines: replay.lines.into(),
+            hash: replay.hash,
+            started: None,
+            divergence: None,
+        }
+    }
+
+    /// The next line, regardless of when it was recorded.
+    pub(crate) fn pop(&mut self) -> Option<Line<Msg>> {
+        self.lines.pop_front()
+    }
+
+    /// The next line, once as much time has passed since the first call as it did while recording.
+    pub(crate) fn pop_due(&mut self) -> Option<Line<Msg>> {
+        let started = *self.started.get_or_insert_with(Instant::now);
+        let line = self.lines.front()?;
+        if started.elapsed() < Duration::from_millis(line.at_ms) {
+            return None;
+        }
+        self.lines.pop_front()
+    }
+
+    /// How long until the next line is due.
+    pub(crate) fn until_next(&self) -> Option<Duration> {
+        let started = self.started?;
+        let at = Duration::from_millis(self.lines.front()?.at_ms);
+        Some(at.saturating_sub(started.elapsed()))
+    }
+
+    /// Compare the model after the line at `frame` with its recorded hash, keeping the first mismatch.
+    pub(crate) fn check(&mut self, frame: u64, expected: Option<u64>, model: &M) {
+        let (Some(hash), Some(expected)) = (self.hash, expected) else {
+            return;
+        };
+        let actual = hash(model);
+        if actual != expected && self.divergence.is_none() {
+            self.divergence = Some(Divergence {
+                index: self.total - self.lines.len() - 1,
+                frame,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    /// Draw the replay's progress.
+    pub(crate) fn show(&self, ctx: &egui::Context) {
+        egui::TopBottomPanel::bottom("chai_tea_replay").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let played = self.total - self.lines.len();
+                if self.lines.is_empty() {
+                    ui.label(format!("⏹ replayed {played} messages"));
+                } else {
+                    ui.label(format!("⏵ replaying {played}/{}", self.total));
+                }
+                if let Some(divergence) = &self.divergence {
+                    ui.colored_label(ui.visuals().error_fg_color, divergence.to_string());
+                }
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::Simulator;
+
+    /// A log kept in memory, readable while the recorder still owns a handle to it.
+    #[derive(Clone, Default)]
+    struct Log(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Log {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct DiskFull;
+
+    impl Write for DiskFull {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(msgs: &[i32]) -> Vec<u8> {
+        let log = Log::default();
+        let mut recorder = Recorder::<i32, i32>::from_writer(log.clone()).with_model_hash();
+        let mut model = 0;
+        for (frame, msg) in msgs.iter().enumerate() {
+            let encoded = recorder.encode(msg).unwrap().unwrap();
+            model += msg;
+            recorder.write(frame as u64, encoded, &model).unwrap();
+        }
+        recorder.flush().unwrap();
+        log.0.take()
+    }
+
+    fn play(log: &[u8], update: fn(i32, i32) -> i32) -> Option<Divergence> {
+        let replay = Replay::<i32, i32>::from_reader(log)
+            .unwrap()
+            .with_model_hash();
+        let mut player = Player::new(replay);
+        let mut model = 0;
+        while let Some(line) = player.pop() {
+            model = update(model, line.msg);
+            player.check(line.frame, line.hash, &model);
+        }
+        player.divergence
+    }
+
+    #[test]
+    fn a_faithful_replay_doesnt_diverge() {
+        let log = record(&[1, 2, 3]);
+
+        assert_eq!(Replay::<i32, i32>::from_reader(&log[..]).unwrap().len(), 3);
+        assert_eq!(play(&log, |n, m| n + m), None);
+    }
+
+    #[test]
+    fn the_first_divergence_is_reported() {
+        let log = record(&[1, 2, 3]);
+
+        // agrees on the first message, then goes its own way for good
+        let divergence = play(&log, |n, m| if n == 0 { n + m } else { n * m }).unwrap();
+
+        assert_eq!((divergence.index, divergence.frame), (1, 1));
+        assert_eq!(divergence.expected, key_of(3));
+        assert_eq!(divergence.actual, key_of(2));
+    }
+
+    #[test]
+    fn a_log_without_hashes_never_diverges() {
+        let log = Log::default();
+        let mut recorder = Recorder::<i32, i32>::from_writer(log.clone());
+        let encoded = recorder.encode(&1).unwrap().unwrap();
+        recorder.write(0, encoded, &1).unwrap();
+
+        assert_eq!(play(&log.0.take(), |_, _| 99), None);
+    }
+
+    #[test]
+    fn truncated_or_garbage_logs_dont_load() {
+        let log = record(&[1, 2]);
+        let truncated = &log[..log.len() - 5];
+
+        let err = Replay::<i32, i32>::from_reader(truncated).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let err = Replay::<i32, i32>::from_reader(&b"not json\n"[..])
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            Replay::<i32, i32>::from_reader(&b"\n\n"[..])
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn a_write_error_stops_recording_and_reaches_on_error() {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&errors);
+        let mut sim = Simulator::new(
+            || (0, vec![]),
+            || (),
+            |n: i32, m: i32| (n + m, vec![]),
+            |(), _, _| {},
+        )
+        .with_recorder(Recorder::from_writer(DiskFull))
+        .with_on_error(move |err| log.borrow_mut().push(err));
+
+        sim.dispatch(1);
+        sim.step();
+        sim.dispatch(2);
+        sim.step();
+
+        assert_eq!(*sim.model(), 3);
+        let errors = errors.borrow();
+        assert_eq!(errors.len(), 1, "only the first failure is reported");
+        assert!(
+            matches!(&errors[0], Error::RecordingStopped { source } if source.to_string() == "disk full")
+        );
+    }
+
+    #[test]
+    fn an_unserializable_message_is_skipped() {
+        type Msg = HashMap<(u8, u8), u8>; // JSON object keys must be strings
+        let log = Log::default();
+        let recorder = Recorder::<(), Msg>::from_writer(log.clone());
+
+        let bad = recorder.encode(&HashMap::from([((1, 2), 3)]));
+        assert!(matches!(bad, Err(Error::UnrecordableMessage { .. })));
+        assert!(recorder.encode(&HashMap::new()).unwrap().is_some());
+    }
+}
@@ -0,0 +1,788 @@
+//! The dispatch core shared by the eframe app and the headless [`Simulator`](crate::Simulator).
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use eframe::egui;
+
+use crate::CancelToken;
+use crate::budget::{self, Budget};
+use crate::channel::{Channel, Inbox};
+#[cfg(feature = "tokio")]
+use crate::cmd::FutureTask;
+use crate::cmd::{Effect, Entry, Task};
+use crate::coalesce::{CoalesceFn, keep_newest};
+use crate::debugger::Debugger;
+use crate::history::History;
+use crate::pace::Pacer;
+#[cfg(feature = "persistence")]
+use crate::persist::Store;
+#[cfg(feature = "serde")]
+use crate::record::{Player, Recorder, Replay};
+#[cfg(feature = "tokio")]
+use crate::spawner::OwnedTokio;
+use crate::spawner::Spawner;
+use crate::sub::{ActiveSubs, Sub};
+use crate::timer::Timers;
+use crate::window::{Window, WindowEvents};
+use crate::{ChaiSender, Cmd};
+
+type OwnedFn<M, Msg, C> = Box<dyn Fn(M, Msg) -> (M, Cmd<Msg, C>)>;
+type InPlaceFn<M, Msg, C> = Box<dyn Fn(&mut M, Msg) -> Cmd<Msg, C>>;
+pub(crate) type RunCmdFn<S, Msg, C> = Box<dyn Fn(C, &mut S, ChaiSender<Msg>)>;
+pub(crate) type SubsFn<M, Msg> = Box<dyn Fn(&M) -> Sub<Msg>>;
+pub(crate) type ExitFn<M, S> = Box<dyn FnOnce(&M, &mut S)>;
+pub(crate) type ErrorFn = Box<dyn Fn(crate::Error)>;
+
+/// `update`, in either of the shapes an app can write it.
+pub(crate) enum Update<M, Msg, C> {
+    /// `Fn(M, Msg) -> (M, R)`: the model is moved out, and the returned one moved back in.
+    Owned(OwnedFn<M, Msg, C>),
+    /// `Fn(&mut M, Msg) -> R`: the model is changed where it lies.
+    InPlace(InPlaceFn<M, Msg, C>),
+}
+
+impl<M, Msg, C> Update<M, Msg, C> {
+    pub(crate) fn owned<R, F>(update: F) -> Self
+    where
+        R: Into<Cmd<Msg, C>>,
+        F: Fn(M, Msg) -> (M, R) + 'static,
+    {
+        Self::Owned(Box::new(move |model, msg| {
+            let (model, cmd) = update(model, msg);
+            (model, cmd.into())
+        }))
+    }
+
+    pub(crate) fn in_place<R, F>(update: F) -> Self
+    where
+        R: Into<Cmd<Msg, C>>,
+        F: Fn(&mut M, Msg) -> R + 'static,
+    {
+        Self::InPlace(Box::new(move |model, msg| update(model, msg).into()))
+    }
+
+    fn call(&self, model: &mut Slot<M>, msg: Msg) -> Cmd<Msg, C> {
+        match self {
+            Self::Owned(update) => {
+                let (new_model, cmd) = update(model.take(), msg);
+                model.put(new_model);
+                cmd
+            }
+            Self::InPlace(update) => update(model.get_mut(), msg),
+        }
+    }
+}
+
+/// The model, with room to move it out while an owned `update` runs.
+///
+/// Saves asking apps for a `Default` model just to have something to leave in its place.
+pub(crate) struct Slot<M>(Option<M>);
+
+impl<M> Slot<M> {
+    pub(crate) fn get(&self) -> &M {
+        self.0
+            .as_ref()
+            .expect("the model is only moved out during update")
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut M {
+        self.0
+            .as_mut()
+            .expect("the model is only moved out during update")
+    }
+
+    pub(crate) fn into_inner(self) -> M {
+        self.0.expect("the model is only moved out during update")
+    }
+
+    fn take(&mut self) -> M {
+        self.0.take().expect("update can't re-enter itself")
+    }
+
+    fn put(&mut self, model: M) {
+        self.0 = Some(model);
+    }
+}
+
+/// Owns the model and drives one frame of the Elm loop: collect messages, run `update`, run commands.
+pub(crate) struct Runtime<M, S, C, Msg> {
+    pub(crate) model: Slot<M>,
+    pub(crate) sync_state: S,
+    pub(crate) messages: Vec<Msg>,
+    /// Messages from window events, kept apart so the debugger never holds them back.
+    window_msgs: Vec<Msg>,
+    update: Update<M, Msg, C>,
+    run_cmd: RunCmdFn<S, Msg, C>,
+    init_cmd: Cmd<Msg, C>,
+    started: bool,
+    pub(crate) chai_tx: ChaiSender<Msg>,
+    msg_rx: Inbox<Msg>,
+    /// Messages a [`Budget`] held back, handled first next frame.
+    backlog: VecDeque<Msg>,
+    budget: Option<Budget>,
+    coalesce: Option<CoalesceFn<Msg>>,
+    subscriptions: SubsFn<M, Msg>,
+    active_subs: ActiveSubs,
+    subs_started: bool,
+    spawner: Arc<dyn Spawner>,
+    /// The runtime behind the default spawner, shut down on [`exit`](Runtime::exit).
+    #[cfg(feature = "tokio")]
+    tokio: Option<OwnedTokio>,
+    tasks: ActiveTasks,
+    timers: Timers<Msg>,
+    pacer: Pacer<Msg>,
+    window_events: Option<WindowEvents<Msg>>,
+    window: Option<Window<M>>,
+    on_exit: Option<ExitFn<M, S>>,
+    debugger: Option<Debugger<M, Msg>>,
+    history: Option<History<M, Msg>>,
+    frame: u64,
+    #[cfg(feature = "serde")]
+    recorder: Option<Recorder<M, Msg>>,
+    #[cfg(feature = "serde")]
+    player: Option<Player<M, Msg>>,
+    #[cfg(feature = "persistence")]
+    store: Option<Box<dyn Store<M>>>,
+    on_error: Option<ErrorFn>,
+}
+
+impl<M, S, C, Msg> Runtime<M, S, C, Msg>
+where
+    Msg: 'static,
+{
+    pub(crate) fn new<Fcmd>(
+        (model, init_cmd): (M, Cmd<Msg, C>),
+        sync_state: S,
+        update: Update<M, Msg, C>,
+        run_cmd: Fcmd,
+    ) -> Self
+    where
+        Fcmd: Fn(C, &mut S, ChaiSender<Msg>) + 'static,
+    {
+        let (msg_tx, msg_rx) = Channel::unbounded().open();
+        #[cfg(feature = "tokio")]
+        let tokio = OwnedTokio::new();
+        #[cfg(feature = "tokio")]
+        let spawner: Arc<dyn Spawner> = tokio.spawner();
+        #[cfg(not(feature = "tokio"))]
+        let spawner: Arc<dyn Spawner> = Arc::new(crate::ThreadSpawner);
+
+        Self {
+            model: Slot(Some(model)),
+            sync_state,
+            messages: Vec::new(),
+            window_msgs: Vec::new(),
+            update,
+            run_cmd: Box::new(run_cmd),
+            init_cmd,
+            started: false,
+            chai_tx: ChaiSender::from_transport(msg_tx).with_spawner(Arc::clone(&spawner)),
+            msg_rx,
+            backlog: VecDeque::new(),
+            budget: None,
+            coalesce: None,
+            subscriptions: Box::new(|_| Sub::none()),
+            active_subs: ActiveSubs::new(),
+            subs_started: false,
+            spawner,
+            #[cfg(feature = "tokio")]
+            tokio: Some(tokio),
+            tasks: ActiveTasks::default(),
+            timers: Timers::new(),
+            pacer: Pacer::new(),
+            window_events: None,
+            window: None,
+            on_exit: None,
+            debugger: None,
+            history: None,
+            frame: 0,
+            #[cfg(feature = "serde")]
+            recorder: None,
+            #[cfg(feature = "serde")]
+            player: None,
+            #[cfg(feature = "persistence")]
+            store: None,
+            on_error: None,
+        }
+    }
+
+    pub(crate) fn with_subscriptions<Fsubs>(mut self, subscriptions: Fsubs) -> Self
+    where
+        Fsubs: Fn(&M) -> Sub<Msg> + 'static,
+    {
+        self.subscriptions = Box::new(subscriptions);
+        self
+    }
+
+    pub(crate) fn with_spawner(mut self, spawner: Arc<dyn Spawner>) -> Self {
+        self.chai_tx = self.chai_tx.with_spawner(Arc::clone(&spawner));
+        self.spawner = spawner;
+        #[cfg(feature = "tokio")]
+        {
+            self.tokio = None;
+        }
+        self
+    }
+
+    /// Queue messages from senders in `channel` instead of the default unbounded one.
+    pub(crate) fn with_channel(mut self, channel: Channel<Msg>) -> Self {
+        let (msg_tx, msg_rx) = channel.open();
+        self.chai_tx = ChaiSender::from_transport(msg_tx).with_spawner(Arc::clone(&self.spawner));
+        self.msg_rx = msg_rx;
+        self
+    }
+
+    /// Stop handling messages each frame once `budget` is spent.
+    pub(crate) fn with_budget(mut self, budget: Budget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Pass only the newest message of each key `coalesce` gives to `update` each frame.
+    pub(crate) fn with_coalesce(mut self, coalesce: CoalesceFn<Msg>) -> Self {
+        self.coalesce = Some(coalesce);
+        self
+    }
+
+    /// Debounce and throttle view messages as `pacer` says.
+    pub(crate) fn with_pacer(mut self, pacer: Pacer<Msg>) -> Self {
+        self.pacer = pacer;
+        self
+    }
+
+    /// Also debounce the view messages `key` picks out, see [`Pacer::debounce`].
+    pub(crate) fn debounce<K, F>(mut self, quiet: Duration, key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        self.pacer.debounce(quiet, key);
+        self
+    }
+
+    /// Also throttle the view messages `key` picks out, see [`Pacer::throttle`].
+    pub(crate) fn throttle<K, F>(mut self, interval: Duration, key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        self.pacer.throttle(interval, key);
+        self
+    }
+
+    /// Turn window events into messages with `window_events`, queued after `view`'s.
+    pub(crate) fn with_window_events(mut self, window_events: WindowEvents<Msg>) -> Self {
+        self.window_events = Some(window_events);
+        self
+    }
+
+    /// Derive the window's properties from the model with `window`.
+    pub(crate) fn with_window(mut self, window: Window<M>) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    /// Bring the window's properties in line with the model's.
+    fn sync_window(&mut self) {
+        if let Some(window) = &mut self.window {
+            let model = self.model.get();
+            self.chai_tx.with_ctx(|ctx| window.sync(model, ctx));
+        }
+    }
+
+    /// Repaint when the next timer is due, however long it is until then.
+    fn wake_for_timers(&self) {
+        if let Some(next) = self.timers.next() {
+            let wait = next.saturating_duration_since(Instant::now());
+            self.chai_tx.with_ctx(|ctx| ctx.request_repaint_after(wait));
+        }
+    }
+
+    /// Messages waiting for `update`: held back by the budget, or not yet drained.
+    pub(crate) fn queue_depth(&self) -> usize {
+        self.backlog.len() + self.msg_rx.len()
+    }
+
+    /// Hand out the commands returned by `init`. Empty after the first call.
+    pub(crate) fn take_init_cmds(&mut self) -> Cmd<Msg, C> {
+        std::mem::take(&mut self.init_cmd)
+    }
+
+    /// Call `on_exit` when the app shuts down, see [`exit`](Runtime::exit).
+    pub(crate) fn with_on_exit(mut self, on_exit: ExitFn<M, S>) -> Self {
+        self.on_exit = Some(on_exit);
+        self
+    }
+
+    /// Whether the default spawner has started its tokio runtime.
+    #[cfg(all(test, feature = "tokio"))]
+    pub(crate) fn tokio_started(&self) -> bool {
+        self.tokio
+            .as_ref()
+            .is_some_and(|tokio| tokio.spawner().is_running())
+    }
+
+    /// Hand the final model and the `SyncState` to `on_exit`, then shut down the default tokio
+    /// runtime. Only the first call does anything.
+    pub(crate) fn exit(&mut self) {
+        if let Some(on_exit) = self.on_exit.take() {
+            on_exit(self.model.get(), &mut self.sync_state);
+        }
+        #[cfg(feature = "tokio")]
+        if let Some(tokio) = &self.tokio {
+            tokio.shutdown();
+        }
+    }
+
+    /// On this runtime's first frame, give its senders the window's context and hand out the
+    /// commands returned by `init`. Does nothing on later frames.
+    pub(crate) fn start(&mut self, ctx: &egui::Context) -> Cmd<Msg, C> {
+        if self.started {
+            return Cmd::none();
+        }
+        self.started = true;
+        self.chai_tx.set_ctx(ctx);
+        self.take_init_cmds()
+    }
+
+    /// Record every update and draw the debugger panel, see [`Debugger`].
+    ///
+    /// Takes the debugger's constructor, since its first snapshot is the model as it is now.
+    pub(crate) fn with_debugger(mut self, debugger: fn(&M) -> Debugger<M, Msg>) -> Self {
+        self.debugger = Some(debugger(self.model.get()));
+        self
+    }
+
+    /// Keep undo/redo history, see [`History`].
+    pub(crate) fn with_history(mut self, history: History<M, Msg>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Log every processed message, see [`Recorder`].
+    #[cfg(feature = "serde")]
+    pub(crate) fn with_recorder(mut self, recorder: Recorder<M, Msg>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Play back `replay` instead of taking live messages, see [`Replay`].
+    #[cfg(feature = "serde")]
+    pub(crate) fn with_replay(mut self, replay: Replay<M, Msg>) -> Self {
+        self.player = Some(Player::new(replay));
+        self
+    }
+
+    /// Hand the errors the runtime recovers from to `on_error`. Set it before anything that
+    /// can fail, like [`with_store`](Runtime::with_store).
+    pub(crate) fn with_on_error(mut self, on_error: ErrorFn) -> Self {
+        self.on_error = Some(on_error);
+        self
+    }
+
+    fn report(&self, result: Result<(), crate::Error>) {
+        if let (Err(err), Some(on_error)) = (result, &self.on_error) {
+            on_error(err);
+        }
+    }
+
+    /// Restore the model from `storage`, and save it there from then on.
+    #[cfg(feature = "persistence")]
+    pub(crate) fn with_store(
+        mut self,
+        store: Box<dyn Store<M>>,
+        storage: Option<&dyn eframe::Storage>,
+    ) -> Self {
+        let restored = store.restore(self.model.get_mut(), storage);
+        self.report(restored);
+        self.store = Some(store);
+        self
+    }
+
+    /// Write the persisted part of the model to `storage`, if persistence is on.
+    #[cfg(feature = "persistence")]
+    pub(crate) fn save(&self, storage: &mut dyn eframe::Storage) {
+        if let Some(store) = &self.store {
+            self.report(store.save(self.model.get(), storage));
+        }
+    }
+
+    /// Render `view`, against a past model if the debugger is scrubbing.
+    pub(crate) fn view<Fview>(&mut self, ctx: &egui::Context, view: Fview)
+    where
+        Fview: Fn(&egui::Context, &M, &mut Vec<Msg>),
+    {
+        #[cfg(feature = "serde")]
+        if let Some(player) = &self.player {
+            player.show(ctx);
+        }
+
+        let model = match &mut self.debugger {
+            Some(debugger) => {
+                debugger.show(ctx);
+                debugger.shown(self.model.get())
+            }
+            None => self.model.get(),
+        };
+        view(ctx, model, &mut self.messages);
+
+        if let Some(window_events) = &mut self.window_events {
+            window_events.poll(ctx, &mut self.window_msgs);
+        }
+    }
+
+    /// Feed view messages, then async messages, through `update`, appending the commands to `cmds`.
+    pub(crate) fn update(&mut self, mut cmds: Cmd<Msg, C>) -> Cmd<Msg, C> {
+        self.frame += 1;
+
+        #[cfg(feature = "serde")]
+        if self.player.is_some() {
+            return self.update_replay();
+        }
+
+        //get the messages deferred by the budget, then view messages, window events, due timers,
+        //and async messages
+        let deferred = std::mem::take(&mut self.backlog);
+        let view_msgs = self.messages.drain(..).collect();
+        let view_msgs = self.pacer.pace(view_msgs, &mut self.timers);
+        let window_msgs = std::mem::take(&mut self.window_msgs);
+        let due = self.timers.pop_due(Instant::now());
+        let mut msgs: VecDeque<_> = match &mut self.debugger {
+            // the budget still applies, but to what the debugger lets through, so the channel
+            // is drained for it to hold while paused
+            Some(debugger) => {
+                let async_msgs = due.into_iter().chain(self.msg_rx.drain()).collect();
+                debugger
+                    .gate(deferred.into(), view_msgs, window_msgs, async_msgs)
+                    .into()
+            }
+            None => {
+                let mut msgs = deferred;
+                msgs.extend(view_msgs);
+                msgs.extend(window_msgs);
+                msgs.extend(due);
+                // a budget pulls from the channel as it goes, unless everything has to be
+                // in view to be coalesced
+                if self.budget.is_none() || self.coalesce.is_some() {
+                    msgs.extend(self.msg_rx.drain());
+                }
+                msgs
+            }
+        };
+        if let Some(key) = &self.coalesce {
+            keep_newest(&mut msgs, key);
+        }
+
+        //handle them, until the budget runs out
+        let mut spending = self.budget.map(Budget::start);
+        let mut model_changed = false;
+        loop {
+            let next = match msgs.pop_front() {
+                Some(msg) => Some(msg),
+                None if self.debugger.is_none() && spending.is_some() => self.msg_rx.pop(),
+                None => None,
+            };
+            let Some(msg) = next else { break };
+
+            cmds.append(self.apply(msg));
+            model_changed = true;
+            if spending
+                .as_mut()
+                .is_some_and(|spending| !spending.handled())
+            {
+                break;
+            }
+        }
+        self.backlog = msgs;
+
+        let depth = self.queue_depth();
+        self.chai_tx.with_ctx(|ctx| {
+            budget::set_queue_depth(ctx, depth);
+            if spending.is_some() && depth > 0 {
+                ctx.request_repaint();
+            }
+        });
+        self.wake_for_timers();
+        self.sync_window();
+
+        #[cfg(feature = "serde")]
+        if let Some(recorder) = &mut self.recorder {
+            let flushed = recorder.flush();
+            self.report(flushed);
+        }
+
+        //start and stop subscriptions to match the new model
+        if model_changed || !self.subs_started {
+            self.subs_started = true;
+            let sub = (self.subscriptions)(self.model.get());
+            self.active_subs.diff(sub, &self.chai_tx);
+        }
+
+        cmds
+    }
+
+    /// Run one message through `update`, letting the debugger and recorder see it.
+    pub(crate) fn apply(&mut self, msg: Msg) -> Cmd<Msg, C> {
+        let label = self.debugger.as_ref().map(|d| d.describe(&msg));
+        #[cfg(feature = "serde")]
+        let encoded = match self.recorder.as_ref().map(|r| r.encode(&msg)) {
+            Some(Ok(encoded)) => encoded,
+            Some(Err(err)) => {
+                self.report(Err(err));
+                None
+            }
+            None => None,
+        };
+
+        let needs_update = match &mut self.history {
+            Some(history) => history.prepare(self.model.get_mut(), &msg),
+            None => true,
+        };
+        let cmds = if needs_update {
+            self.update.call(&mut self.model, msg)
+        } else {
+            Cmd::none()
+        };
+
+        if let (Some(debugger), Some(label)) = (&mut self.debugger, label) {
+            debugger.record(label, self.model.get());
+        }
+        #[cfg(feature = "serde")]
+        if let (Some(recorder), Some(encoded)) = (&mut self.recorder, encoded) {
+            let written = recorder.write(self.frame, encoded, self.model.get());
+            self.report(written);
+        }
+
+        cmds
+    }
+
+    /// Apply the replayed messages that are due, dropping live ones and every command.
+    #[cfg(feature = "serde")]
+    fn update_replay(&mut self) -> Cmd<Msg, C> {
+        self.messages.clear();
+        self.window_msgs.clear();
+        drop(self.msg_rx.drain());
+
+        while let Some(line) = self.player.as_mut().and_then(Player::pop_due) {
+            drop(self.apply(line.msg));
+            if let Some(player) = &mut self.player {
+                player.check(line.frame, line.hash, self.model.get());
+            }
+        }
+
+        if let Some(wait) = self.player.as_ref().and_then(Player::until_next) {
+            self.chai_tx.with_ctx(|ctx| ctx.request_repaint_after(wait));
+        }
+        self.sync_window();
+
+        Cmd::none()
+    }
+}
+
+impl<M, S, C, Msg> Runtime<M, S, C, Msg>
+where
+    Msg: 'static,
+{
+    /// Carry out every effect that stays on the UI thread, for apps whose messages can't be sent
+    /// to others. Work for other threads can't report back, so it's dropped and reported as
+    /// [`Error::CommandDropped`](crate::Error::CommandDropped).
+    pub(crate) fn run_local_cmds(&mut self, cmds: Cmd<Msg, C>) {
+        let mut dropped = 0;
+        self.carry_out(cmds, |runtime, work, _, _| {
+            let command = match work {
+                Threaded::Perform(_) => "Cmd::perform",
+                #[cfg(feature = "tokio")]
+                Threaded::Future(_) => "Cmd::future",
+            };
+            runtime.report(Err(crate::Error::CommandDropped { command }));
+            dropped += 1;
+        });
+        debug_assert_eq!(
+            dropped, 0,
+            "run_local can't run Cmd::perform or Cmd::future; start the app with run"
+        );
+    }
+
+    /// Carry out every effect, handing app-defined commands to `run_cmd` with their own sender,
+    /// and work for other threads to `spawn` with the sender and token it should use.
+    fn carry_out<F>(&mut self, cmds: Cmd<Msg, C>, mut spawn: F)
+    where
+        F: FnMut(&Self, Threaded<Msg>, ChaiSender<Msg>, CancelToken),
+    {
+        // effects started under the same key in one batch share a token
+        let mut started = HashMap::new();
+
+        for Entry { key, effect } in cmds.effects {
+            let mut tx = ChaiSender::clone(&self.chai_tx);
+            let token = match key {
+                Some(key) => started
+                    .entry(key)
+                    .or_insert_with(|| {
+                        self.timers.cancel(key);
+                        self.tasks.restart(key)
+                    })
+                    .clone(),
+                None => CancelToken::new(),
+            };
+            if key.is_some() {
+                tx = tx.with_token(token.clone());
+            }
+
+            match effect {
+                Effect::Perform(task) => spawn(self, Threaded::Perform(task), tx, token),
+                #[cfg(feature = "tokio")]
+                Effect::Future(future) => spawn(self, Threaded::Future(future), tx, token),
+                Effect::User(cmd) => (self.run_cmd)(cmd, &mut self.sync_state, tx),
+                Effect::Deliver(at, msg) => self.timers.schedule(at, key, msg),
+                Effect::Viewport(command) => {
+                    self.chai_tx.with_ctx(|ctx| ctx.send_viewport_cmd(command))
+                }
+                Effect::Cancel(key) => {
+                    started.remove(&key);
+                    self.tasks.cancel(key);
+                    self.timers.cancel(key);
+                }
+            }
+        }
+
+        drop(started);
+        self.tasks.prune();
+        self.wake_for_timers();
+    }
+}
+
+impl<M, S, C, Msg> Runtime<M, S, C, Msg>
+where
+    Msg: Send + 'static,
+{
+    /// Carry out every effect, running work for other threads on the app's [`Spawner`].
+    pub(crate) fn run_cmds(&mut self, cmds: Cmd<Msg, C>) {
+        self.carry_out(cmds, |runtime, work, tx, token| match work {
+            Threaded::Perform(task) => runtime.spawner.spawn(Box::new(move || {
+                tx.send(task(token)).ok();
+            })),
+            #[cfg(feature = "tokio")]
+            Threaded::Future(future) => runtime.spawner.spawn_future(Box::pin(async move {
+                let future = future(token.clone());
+                if let Some(msg) = crate::cancel::until_cancelled(token, future).await {
+                    tx.send(msg).ok();
+                }
+            })),
+        });
+    }
+}
+
+/// An effect that runs off the UI thread, and so needs `Msg: Send` to report back.
+enum Threaded<Msg> {
+    Perform(Task<Msg>),
+    #[cfg(feature = "tokio")]
+    Future(FutureTask<Msg>),
+}
+
+/// Tokens of the keyed commands that may still be running.
+#[derive(Default)]
+struct ActiveTasks {
+    running: HashMap<u64, CancelToken>,
+}
+
+impl ActiveTasks {
+    /// Cancel anything running under `key` and hand out a fresh token for its replacement.
+    fn restart(&mut self, key: u64) -> CancelToken {
+        let token = CancelToken::new();
+        if let Some(old) = self.running.insert(key, token.clone()) {
+            old.cancel();
+        }
+        token
+    }
+
+    fn cancel(&mut self, key: u64) {
+        if let Some(token) = self.running.remove(&key) {
+            token.cancel();
+        }
+    }
+
+    /// Forget tasks that have finished and dropped their end of the token.
+    fn prune(&mut self) {
+        self.running.retain(|_, token| !token.is_orphaned());
+    }
+}
+
+impl Drop for ActiveTasks {
+    fn drop(&mut self) {
+        for token in self.running.values() {
+            token.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::rc::Rc;
+
+    use super::*;
+
+    // messages that can't cross threads, as `run_local` apps have
+    type Msg = Rc<u32>;
+    type Local = Runtime<(), Vec<u32>, u32, Msg>;
+
+    fn local(init_cmd: Cmd<Msg, u32>) -> Local {
+        let update = Update::owned(|(), _: Msg| ((), Cmd::none()));
+        Runtime::new(
+            ((), init_cmd),
+            Vec::new(),
+            update,
+            |cmd, ran: &mut Vec<u32>, _| ran.push(cmd),
+        )
+    }
+
+    #[test]
+    fn run_local_hands_app_commands_to_run_cmd() {
+        let mut runtime = local(Cmd::none());
+
+        runtime.run_local_cmds(Cmd::batch([Cmd::user(1), Cmd::user(2).with_key("save")]));
+
+        assert_eq!(runtime.sync_state, [1, 2]);
+    }
+
+    #[test]
+    fn run_local_reports_the_threaded_work_it_drops() {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let log = Rc::clone(&errors);
+        let mut runtime = local(Cmd::none())
+            .with_on_error(Box::new(move |err| log.borrow_mut().push(err.to_string())));
+        // a `Send` child's work can reach a local app through `map`
+        let cmd = Cmd::perform(|| 1, |n: u32| n).map(Rc::new);
+
+        let run = panic::catch_unwind(AssertUnwindSafe(|| runtime.run_local_cmds(cmd)));
+
+        assert_eq!(run.is_err(), cfg!(debug_assertions));
+        assert_eq!(
+            *errors.borrow(),
+            ["dropped Cmd::perform: run_local can't run it off the UI thread"]
+        );
+    }
+
+    #[test]
+    fn every_runtime_starts_once_with_its_own_init_commands() {
+        let ctx = egui::Context::default();
+        // the second pair is built after the first is dropped, as when an app is run again
+        for _ in 0..2 {
+            let mut runtimes = [local(Cmd::user(1)), local(Cmd::user(2))];
+
+            for (runtime, init) in runtimes.iter_mut().zip([1, 2]) {
+                let cmds = runtime.start(&ctx);
+                assert_eq!(cmds.user_cmds().collect::<Vec<_>>(), [&init]);
+                assert!(runtime.start(&ctx).is_none());
+
+                let mut has_ctx = false;
+                runtime.chai_tx.with_ctx(|_| has_ctx = true);
+                assert!(has_ctx);
+            }
+        }
+    }
+}
@@ -0,0 +1,445 @@
+//! A headless driver for async chai-tea programs.
+
+use std::hash::Hash;
+use std::time::Duration;
+
+use crate::coalesce;
+#[cfg(feature = "serde")]
+use crate::record::{Divergence, Player, Recorder, Replay};
+use crate::runtime::{Runtime, Update};
+use crate::{Budget, ChaiSender, Channel, Cmd, History, Program, Spawner, Sub};
+
+/// Runs the same loop as [`run_async`](crate::run_async), minus the window.
+///
+/// A `Simulator` owns the model and `SyncState`, queues messages as if `view` had pushed them,
+/// drains messages sent through its [`ChaiSender`], and executes commands through your real
+/// `run_cmd`. Every [`step`](Simulator::step) dispatches in exactly the order a frame of the
+/// eframe app does, so tests see production behavior.
+///
+/// # Example
+/// ```
+/// enum Msg { Start, Tick(u32) }
+/// enum Cmd { Tick }
+///
+/// #[derive(Default)]
+/// struct Model { ticks: u32 }
+///
+/// fn update(m: Model, msg: Msg) -> (Model, Vec<Cmd>) {
+///     match msg {
+///         Msg::Start => (m, vec![Cmd::Tick]),
+///         Msg::Tick(n) => (Model { ticks: m.ticks + n }, vec![]),
+///     }
+/// }
+///
+/// fn run_cmd(cmd: Cmd, _sync: &mut (), tx: chai_tea::ChaiSender<Msg>) {
+///     match cmd {
+///         Cmd::Tick => {
+///             std::thread::spawn(move || tx.send(Msg::Tick(1)).ok());
+///         }
+///     }
+/// }
+///
+/// let mut sim = chai_tea::Simulator::new(|| (Model::default(), vec![]), || (), update, run_cmd);
+/// sim.dispatch(Msg::Start);
+///
+/// let cmds = sim.update();
+/// assert!(matches!(cmds.user_cmds().collect::<Vec<_>>()[..], [Cmd::Tick]));
+/// sim.run_cmds(cmds);
+///
+/// let timeout = std::time::Duration::from_secs(1);
+/// assert!(sim.step_until(timeout, |m| m.ticks == 1));
+/// ```
+pub struct Simulator<M, S, C, Msg> {
+    runtime: Runtime<M, S, C, Msg>,
+}
+
+impl<M, S, C, Msg> Simulator<M, S, C, Msg>
+where
+    Msg: Send + 'static,
+{
+    /// Build a simulator from the same functions you pass to [`run_async`](crate::run_async).
+    ///
+    /// The commands returned by `init` are emitted by the first [`update`](Simulator::update).
+    pub fn new<Ri, R, Finit, FsyncInit, Fupdate, Fcmd>(
+        init: Finit,
+        sync_state_init: FsyncInit,
+        update: Fupdate,
+        run_cmd: Fcmd,
+    ) -> Self
+    where
+        Ri: Into<Cmd<Msg, C>>,
+        R: Into<Cmd<Msg, C>>,
+        Finit: FnOnce() -> (M, Ri),
+        FsyncInit: FnOnce() -> S,
+        Fupdate: Fn(M, Msg) -> (M, R) + 'static,
+        Fcmd: Fn(C, &mut S, ChaiSender<Msg>) + 'static,
+    {
+        let (model, init_cmd) = init();
+        let init = (model, init_cmd.into());
+        Self {
+            runtime: Runtime::new(init, sync_state_init(), Update::owned(update), run_cmd),
+        }
+    }
+
+    /// Like [`new`](Simulator::new), for an `update` that changes the model in place, as
+    /// [`AppBuilder::update_mut`](crate::AppBuilder::update_mut) takes.
+    ///
+    /// ```
+    /// struct Model { total: u64 } // no `Default` needed
+    ///
+    /// fn update(m: &mut Model, n: u64) {
+    ///     m.total += n;
+    /// }
+    ///
+    /// let init = || (Model { total: 0 }, chai_tea::Cmd::none());
+    /// let mut sim = chai_tea::Simulator::new_mut(init, || (), update, chai_tea::no_run_cmd);
+    /// sim.dispatch(2);
+    /// sim.dispatch(3);
+    /// sim.step();
+    /// assert_eq!(sim.model().total, 5);
+    /// ```
+    pub fn new_mut<Ri, R, Finit, FsyncInit, Fupdate, Fcmd>(
+        init: Finit,
+        sync_state_init: FsyncInit,
+        update: Fupdate,
+        run_cmd: Fcmd,
+    ) -> Self
+    where
+        Ri: Into<Cmd<Msg, C>>,
+        R: Into<Cmd<Msg, C>>,
+        Finit: FnOnce() -> (M, Ri),
+        FsyncInit: FnOnce() -> S,
+        Fupdate: Fn(&mut M, Msg) -> R + 'static,
+        Fcmd: Fn(C, &mut S, ChaiSender<Msg>) + 'static,
+    {
+        let (model, init_cmd) = init();
+        let init = (model, init_cmd.into());
+        Self {
+            runtime: Runtime::new(init, sync_state_init(), Update::in_place(update), run_cmd),
+        }
+    }
+
+    /// Build a simulator from a [`Program`], subscriptions included.
+    ///
+    /// ```
+    /// # use std::convert::Infallible;
+    /// # use eframe::egui;
+    /// use chai_tea::{ChaiSender, Cmd, Program, Simulator};
+    ///
+    /// struct Log;
+    ///
+    /// impl Program for Log {
+    ///     const TITLE: &'static str = "log";
+    ///
+    ///     type Model = Vec<String>;
+    ///     type Msg = String;
+    ///     type Cmd = Infallible;
+    ///     type Sync = ();
+    ///
+    ///     fn init() -> (Vec<String>, Cmd<String>) {
+    ///         (Vec::new(), Cmd::none())
+    ///     }
+    ///     fn sync_state_init() {}
+    ///     fn update_mut(lines: &mut Vec<String>, line: String) -> Cmd<String> {
+    ///         lines.push(line);
+    ///         Cmd::none()
+    ///     }
+    ///     fn view(_ctx: &egui::Context, _lines: &Vec<String>, _tx: &mut Vec<String>) {}
+    ///     fn run_cmd(cmd: Infallible, _sync: &mut (), _tx: ChaiSender<String>) {
+    ///         match cmd {}
+    ///     }
+    /// }
+    ///
+    /// let mut sim = Simulator::program::<Log>();
+    /// sim.dispatch("hello".to_owned());
+    /// sim.step();
+    /// assert_eq!(sim.model(), &["hello"]);
+    /// ```
+    pub fn program<P>() -> Self
+    where
+        P: Program<Model = M, Msg = Msg, Sync = S, Cmd = C>,
+    {
+        Self::new(P::init, P::sync_state_init, P::update, P::run_cmd)
+            .with_subscriptions(P::subscriptions)
+    }
+
+    /// Drive [`Sub`]scriptions from the model, as [`AppBuilder::subscriptions`](crate::AppBuilder::subscriptions) does.
+    pub fn with_subscriptions<Fsubs>(mut self, subscriptions: Fsubs) -> Self
+    where
+        Fsubs: Fn(&M) -> Sub<Msg> + 'static,
+    {
+        self.runtime = self.runtime.with_subscriptions(subscriptions);
+        self
+    }
+
+    /// Run command work on `spawner`, e.g. an [`InlineSpawner`](crate::InlineSpawner) so
+    /// commands finish before [`run_cmds`](Simulator::run_cmds) returns.
+    pub fn with_spawner<Sp: Spawner + 'static>(mut self, spawner: Sp) -> Self {
+        self.runtime = self.runtime.with_spawner(std::sync::Arc::new(spawner));
+        self
+    }
+
+    /// Queue sent messages in `channel`, as [`AppBuilder::channel`](crate::AppBuilder::channel) does.
+    pub fn with_channel(mut self, channel: Channel<Msg>) -> Self {
+        self.runtime = self.runtime.with_channel(channel);
+        self
+    }
+
+    /// Defer messages past `budget` to later steps, as [`AppBuilder::budget`](crate::AppBuilder::budget) does.
+    pub fn with_budget(mut self, budget: Budget) -> Self {
+        self.runtime = self.runtime.with_budget(budget);
+        self
+    }
+
+    /// Hand `update` only the newest message of each key per step, as
+    /// [`AppBuilder::coalesce`](crate::AppBuilder::coalesce) does.
+    ///
+    /// ```
+    /// use chai_tea::Simulator;
+    ///
+    /// enum Msg { Progress(u32), Log(&'static str) }
+    ///
+    /// fn key(msg: &Msg) -> Option<&'static str> {
+    ///     match msg {
+    ///         Msg::Progress(_) => Some("progress"),
+    ///         Msg::Log(_) => None,
+    ///     }
+    /// }
+    ///
+    /// fn update(mut seen: Vec<String>, msg: Msg) -> (Vec<String>, Vec<()>) {
+    ///     seen.push(match msg {
+    ///         Msg::Progress(p) => format!("{p}%"),
+    ///         Msg::Log(line) => line.to_string(),
+    ///     });
+    ///     (seen, vec![])
+    /// }
+    ///
+    /// let mut sim = Simulator::new(|| (vec![], vec![]), || (), update, |(), _, _| {})
+    ///     .with_coalesce(key);
+    /// let tx = sim.sender();
+    /// tx.send(Msg::Progress(10)).unwrap();
+    /// tx.send(Msg::Log("halfway")).unwrap();
+    /// tx.send(Msg::Progress(50)).unwrap();
+    ///
+    /// sim.step();
+    /// assert_eq!(sim.model(), &["halfway", "50%"]);
+    /// ```
+    pub fn with_coalesce<K, F>(mut self, key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        self.runtime = self.runtime.with_coalesce(coalesce::keyed(key));
+        self
+    }
+
+    /// Debounce dispatched messages, as [`AppBuilder::debounce`](crate::AppBuilder::debounce) does.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use chai_tea::Simulator;
+    ///
+    /// enum Msg { Query(&'static str) }
+    ///
+    /// fn update(searches: Vec<&'static str>, Msg::Query(q): Msg) -> (Vec<&'static str>, Vec<()>) {
+    ///     ([searches, vec![q]].concat(), vec![])
+    /// }
+    ///
+    /// let mut sim = Simulator::new(|| (vec![], vec![]), || (), update, |(), _, _| {})
+    ///     .with_debounce(Duration::from_millis(20), |_| Some("query"));
+    /// for q in ["c", "ch", "cha", "chai"] {
+    ///     sim.dispatch(Msg::Query(q));
+    ///     sim.step();
+    /// }
+    /// assert!(sim.model().is_empty());
+    ///
+    /// assert!(sim.step_until(Duration::from_secs(1), |searches| !searches.is_empty()));
+    /// assert_eq!(sim.model(), &["chai"]);
+    /// ```
+    pub fn with_debounce<K, F>(mut self, quiet: Duration, key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        self.runtime = self.runtime.debounce(quiet, key);
+        self
+    }
+
+    /// Throttle dispatched messages, as [`AppBuilder::throttle`](crate::AppBuilder::throttle) does.
+    pub fn with_throttle<K, F>(mut self, interval: Duration, key: F) -> Self
+    where
+        K: Hash,
+        F: Fn(&Msg) -> Option<K> + 'static,
+    {
+        self.runtime = self.runtime.throttle(interval, key);
+        self
+    }
+
+    /// Keep undo/redo history, as [`AppBuilder::history`](crate::AppBuilder::history) does.
+    pub fn with_history(mut self, history: History<M, Msg>) -> Self {
+        self.runtime = self.runtime.with_history(history);
+        self
+    }
+
+    /// Log every processed message, as [`AppBuilder::recorder`](crate::AppBuilder::recorder) does.
+    #[cfg(feature = "serde")]
+    pub fn with_recorder(mut self, recorder: Recorder<M, Msg>) -> Self {
+        self.runtime = self.runtime.with_recorder(recorder);
+        self
+    }
+
+    /// Hand failures the simulator recovers from to `on_error`, as
+    /// [`AppBuilder::on_error`](crate::AppBuilder::on_error) does.
+    ///
+    /// ```
+    /// use std::cell::RefCell;
+    /// use std::io;
+    /// use std::rc::Rc;
+    /// use chai_tea::{Recorder, Simulator};
+    ///
+    /// struct DiskFull;
+    /// impl io::Write for DiskFull {
+    ///     fn write(&mut self, _: &[u8]) -> io::Result<usize> { Err(io::Error::other("disk full")) }
+    ///     fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    /// }
+    ///
+    /// let errors = Rc::new(RefCell::new(Vec::new()));
+    /// let log = Rc::clone(&errors);
+    /// let mut sim = Simulator::new(|| (0, vec![]), || (), |n: i32, m: i32| (n + m, vec![]), |(), _, _| {})
+    ///     .with_recorder(Recorder::from_writer(DiskFull))
+    ///     .with_on_error(move |err| log.borrow_mut().push(err.to_string()));
+    /// sim.dispatch(1);
+    /// sim.dispatch(2);
+    /// sim.step();
+    ///
+    /// assert_eq!(*sim.model(), 3);
+    /// assert_eq!(*errors.borrow(), ["recording stopped: disk full"]);
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn with_on_error<F>(mut self, on_error: F) -> Self
+    where
+        F: Fn(crate::Error) + 'static,
+    {
+        self.runtime = self.runtime.with_on_error(Box::new(on_error));
+        self
+    }
+
+    /// Feed every message in `replay` through `update`, without running commands or waiting
+    /// for the recorded timings.
+    ///
+    /// Returns the first [`Divergence`] if the replay is checking model hashes; the rest of the
+    /// log is still applied, so the final model can be inspected either way.
+    #[cfg(feature = "serde")]
+    pub fn replay(&mut self, replay: Replay<M, Msg>) -> Result<(), Divergence> {
+        let mut player = Player::new(replay);
+        while let Some(line) = player.pop() {
+            drop(self.runtime.apply(line.msg));
+            player.check(line.frame, line.hash, self.runtime.model.get());
+        }
+        player.divergence.map_or(Ok(()), Err)
+    }
+
+    /// Queue a message, exactly as if `view` had pushed it.
+    pub fn dispatch(&mut self, msg: Msg) {
+        self.runtime.messages.push(msg);
+    }
+
+    /// A sender wired to this simulator, for playing the part of a background worker.
+    pub fn sender(&self) -> ChaiSender<Msg> {
+        ChaiSender::clone(&self.runtime.chai_tx)
+    }
+
+    /// Process one frame's worth of messages and return the emitted commands without running them.
+    pub fn update(&mut self) -> Cmd<Msg, C> {
+        let cmds = self.runtime.take_init_cmds();
+        self.runtime.update(cmds)
+    }
+
+    /// Execute commands through `run_cmd`, as the app does at the end of a frame.
+    pub fn run_cmds(&mut self, cmds: Cmd<Msg, C>) {
+        self.runtime.run_cmds(cmds);
+    }
+
+    /// Process one frame: [`update`](Simulator::update), then [`run_cmds`](Simulator::run_cmds).
+    pub fn step(&mut self) {
+        let cmds = self.update();
+        self.run_cmds(cmds);
+    }
+
+    /// Keep stepping until `done` holds for the model or `timeout` runs out.
+    ///
+    /// Returns whether `done` was reached. Useful for waiting on background commands.
+    pub fn step_until<F>(&mut self, timeout: std::time::Duration, done: F) -> bool
+    where
+        F: Fn(&M) -> bool,
+    {
+        let start = std::time::Instant::now();
+        loop {
+            self.step();
+            if done(self.runtime.model.get()) {
+                return true;
+            }
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    /// The current model.
+    pub fn model(&self) -> &M {
+        self.runtime.model.get()
+    }
+
+    /// How many messages are waiting for a later step, like [`queue_depth`](crate::queue_depth).
+    pub fn queue_depth(&self) -> usize {
+        self.runtime.queue_depth()
+    }
+
+    /// The `SyncState` handed to `run_cmd`.
+    pub fn sync_state(&self) -> &S {
+        &self.runtime.sync_state
+    }
+
+    /// Mutable access to the `SyncState`, e.g. to flip flags a worker is watching.
+    pub fn sync_state_mut(&mut self) -> &mut S {
+        &mut self.runtime.sync_state
+    }
+
+    /// Consume the simulator and return the model.
+    pub fn into_model(self) -> M {
+        self.runtime.model.into_inner()
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokio_starts_with_the_first_future() {
+        enum Msg {
+            Work,
+            Fetch,
+            Done,
+        }
+
+        fn update(done: u32, msg: Msg) -> (u32, Cmd<Msg>) {
+            match msg {
+                Msg::Work => (done, Cmd::perform(|| (), |()| Msg::Done)),
+                Msg::Fetch => (done, Cmd::future(async { Msg::Done })),
+                Msg::Done => (done + 1, Cmd::none()),
+            }
+        }
+
+        let timeout = Duration::from_secs(1);
+        let mut sim = Simulator::new(|| (0, Cmd::none()), || (), update, crate::no_run_cmd);
+        sim.dispatch(Msg::Work);
+        assert!(sim.step_until(timeout, |&done| done == 1));
+        assert!(!sim.runtime.tokio_started());
+
+        sim.dispatch(Msg::Fetch);
+        assert!(sim.step_until(timeout, |&done| done == 2));
+        assert!(sim.runtime.tokio_started());
+    }
+}
@@ -0,0 +1,298 @@
+//! Pluggable executors for command work.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, mpsc};
+use std::task::{Context, Poll, Wake};
+
+/// A unit of blocking work handed to a [`Spawner`].
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// A unit of async work handed to a [`Spawner`].
+pub type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Where command work runs.
+///
+/// The runtime spawns every [`Cmd`](crate::Cmd) effect through one `Spawner`, and `run_cmd` can
+/// use the same one via [`ChaiSender::spawn`](crate::ChaiSender::spawn). Swap it to cap
+/// concurrency with a [`ThreadPool`], or to run work deterministically in tests with an
+/// [`InlineSpawner`].
+pub trait Spawner: Send + Sync {
+    /// Run a blocking job.
+    fn spawn(&self, job: Job);
+
+    /// Run a future to completion.
+    ///
+    /// The default drives it on a job from [`spawn`](Spawner::spawn) with a minimal executor,
+    /// which is fine for futures that don't need a reactor like tokio's.
+    fn spawn_future(&self, future: BoxFuture) {
+        self.spawn(Box::new(move || block_on(future)));
+    }
+}
+
+/// Spawns a fresh OS thread per job. How apps run blocking jobs unless they pick a spawner.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadSpawner;
+
+impl Spawner for ThreadSpawner {
+    fn spawn(&self, job: Job) {
+        std::thread::spawn(job);
+    }
+}
+
+/// Runs every job immediately on the calling thread.
+///
+/// Meant for tests: commands complete before `run_cmds` returns, so their messages are
+/// waiting for the next update.
+///
+/// # Example
+/// ```
+/// use chai_tea::{Cmd, InlineSpawner, Simulator};
+///
+/// #[derive(Default)]
+/// struct Model { answer: u32 }
+/// enum Msg { Ask, Answer(u32) }
+///
+/// fn update(m: Model, msg: Msg) -> (Model, Cmd<Msg>) {
+///     match msg {
+///         Msg::Ask => (m, Cmd::perform(|| 42, Msg::Answer)),
+///         Msg::Answer(answer) => (Model { answer }, Cmd::none()),
+///     }
+/// }
+///
+/// let mut sim = Simulator::new(|| (Model::default(), Cmd::none()), || (), update, chai_tea::no_run_cmd)
+///     .with_spawner(InlineSpawner);
+/// sim.dispatch(Msg::Ask);
+/// sim.step(); // runs the task inline
+/// sim.step(); // delivers its answer
+/// assert_eq!(sim.model().answer, 42);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InlineSpawner;
+
+impl Spawner for InlineSpawner {
+    fn spawn(&self, job: Job) {
+        job();
+    }
+}
+
+/// A fixed set of worker threads sharing one job queue, capping how much work runs at once.
+///
+/// Jobs beyond the worker count wait their turn. A job that panics is abandoned like one on its
+/// own thread would be, and its worker moves on to the next. Workers exit once the pool is
+/// dropped and the queue has drained.
+pub struct ThreadPool {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl ThreadPool {
+    /// Start `workers` threads. At least one is always started.
+    pub fn new(workers: usize) -> Self {
+        let (jobs, queue) = mpsc::channel::<Job>();
+        let queue = Arc::new(Mutex::new(queue));
+
+        for i in 0..workers.max(1) {
+            let queue = Arc::clone(&queue);
+            std::thread::Builder::new()
+                .name(format!("chai-tea-worker-{i}"))
+                .spawn(move || {
+                    loop {
+                        let job = queue.lock().unwrap().recv();
+                        match job {
+                            Ok(job) => {
+                                // the panic is already reported; don't lose the worker to it
+                                let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                            }
+                            Err(_) => return,
+                        }
+                    }
+                })
+                .expect("failed to spawn a worker thread");
+        }
+
+        Self { jobs }
+    }
+}
+
+impl Spawner for ThreadPool {
+    fn spawn(&self, job: Job) {
+        self.jobs.send(job).ok();
+    }
+}
+
+/// Runs futures on a tokio runtime and blocking jobs on its blocking pool.
+///
+/// To run commands on a runtime you already have, pass [`TokioSpawner::from_handle`] to
+/// [`AppBuilder::spawner`](crate::AppBuilder::spawner). Without one, the `tokio` feature runs
+/// futures on a runtime the app starts on first use and shuts down when its window closes.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Debug)]
+pub struct TokioSpawner {
+    handle: tokio::runtime::Handle,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioSpawner {
+    /// Spawn onto the runtime `handle` belongs to. Its owner decides when it shuts down.
+    pub fn from_handle(handle: tokio::runtime::Handle) -> Self {
+        Self { handle }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Spawner for TokioSpawner {
+    fn spawn(&self, job: Job) {
+        self.handle.spawn_blocking(job);
+    }
+
+    fn spawn_future(&self, future: BoxFuture) {
+        self.handle.spawn(future);
+    }
+}
+
+/// The spawner apps get with the `tokio` feature unless they pick one.
+///
+/// Blocking jobs get a thread each, like [`ThreadSpawner`]; futures go to a tokio runtime
+/// started the first time one is spawned, so apps and [`Simulator`](crate::Simulator)s that
+/// never run a future never start one.
+#[cfg(feature = "tokio")]
+pub(crate) struct LazyTokio(Mutex<TokioState>);
+
+#[cfg(feature = "tokio")]
+enum TokioState {
+    Idle,
+    Running(tokio::runtime::Runtime),
+    Stopped,
+}
+
+#[cfg(feature = "tokio")]
+impl LazyTokio {
+    fn new() -> Self {
+        Self(Mutex::new(TokioState::Idle))
+    }
+
+    /// The running runtime's handle, starting it if this is the first future. `None` once
+    /// shut down.
+    fn handle(&self) -> Option<tokio::runtime::Handle> {
+        let mut state = self.0.lock().ok()?;
+        if let TokioState::Idle = *state {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .thread_name("chai-tea")
+                .build()
+                .expect("failed to start the tokio runtime");
+            *state = TokioState::Running(runtime);
+        }
+        match &*state {
+            TokioState::Running(runtime) => Some(runtime.handle().clone()),
+            _ => None,
+        }
+    }
+
+    /// Whether a future has started the runtime, and it's still running.
+    #[cfg(test)]
+    pub(crate) fn is_running(&self) -> bool {
+        matches!(self.0.lock().as_deref(), Ok(TokioState::Running(_)))
+    }
+
+    /// Stop the runtime, if it was started. Doesn't block the closing window on in-flight
+    /// tasks: futures are cancelled at their next await. Futures spawned afterwards never run.
+    fn shutdown(&self) {
+        let Ok(mut state) = self.0.lock() else {
+            return;
+        };
+        if let TokioState::Running(runtime) = std::mem::replace(&mut *state, TokioState::Stopped) {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Spawner for LazyTokio {
+    fn spawn(&self, job: Job) {
+        std::thread::spawn(job);
+    }
+
+    fn spawn_future(&self, future: BoxFuture) {
+        if let Some(handle) = self.handle() {
+            handle.spawn(future);
+        }
+    }
+}
+
+/// The app's hold on its [`LazyTokio`]: senders share the spawner, but only the app shuts it
+/// down, on exit or when dropped.
+#[cfg(feature = "tokio")]
+pub(crate) struct OwnedTokio(Arc<LazyTokio>);
+
+#[cfg(feature = "tokio")]
+impl OwnedTokio {
+    pub(crate) fn new() -> Self {
+        Self(Arc::new(LazyTokio::new()))
+    }
+
+    pub(crate) fn spawner(&self) -> Arc<LazyTokio> {
+        Arc::clone(&self.0)
+    }
+
+    pub(crate) fn shutdown(&self) {
+        self.0.shutdown();
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for OwnedTokio {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// The spawner for senders made outside an app: the current tokio runtime's, if there is one.
+pub(crate) fn default_spawner() -> Arc<dyn Spawner> {
+    #[cfg(feature = "tokio")]
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        return Arc::new(TokioSpawner::from_handle(handle));
+    }
+
+    Arc::new(ThreadSpawner)
+}
+
+struct ThreadWaker(std::thread::Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Poll `future` on the current thread, parking between wakeups.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let waker = Arc::new(ThreadWaker(std::thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn a_panicking_job_doesnt_take_its_worker_down() {
+        let pool = ThreadPool::new(1);
+        let (tx, rx) = mpsc::channel();
+
+        pool.spawn(Box::new(|| panic!("job failed")));
+        pool.spawn(Box::new(move || tx.send("next job ran").unwrap()));
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok("next job ran"));
+    }
+}
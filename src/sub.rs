@@ -0,0 +1,199 @@
+//! Declarative subscriptions, recomputed from the model after every update.
+
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+use crate::cancel::key_of;
+use crate::{CancelToken, ChaiSender};
+
+struct Source<Msg> {
+    key: u64,
+    start: Box<dyn FnOnce(ChaiSender<Msg>, CancelToken)>,
+}
+
+/// The set of recurring message sources an app wants running for a given model.
+///
+/// Return one from your `subscriptions(&Model)` function. After every update the runtime
+/// compares keys with the previous set: new keys are started, missing keys are cancelled,
+/// and keys present in both are left running untouched.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use chai_tea::Sub;
+/// struct Model { running: bool }
+/// enum Msg { Tick }
+///
+/// fn subscriptions(m: &Model) -> Sub<Msg> {
+///     if m.running {
+///         Sub::interval("tick", Duration::from_secs(1), || Msg::Tick)
+///     } else {
+///         Sub::none()
+///     }
+/// }
+/// ```
+pub struct Sub<Msg> {
+    sources: Vec<Source<Msg>>,
+}
+
+impl<Msg> Default for Sub<Msg> {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl<Msg> Sub<Msg> {
+    /// No subscriptions.
+    pub fn none() -> Self {
+        Self {
+            sources: Vec::new(),
+        }
+    }
+
+    /// Combine several subscriptions into one.
+    pub fn batch(subs: impl IntoIterator<Item = Sub<Msg>>) -> Self {
+        Self {
+            sources: subs.into_iter().flat_map(|sub| sub.sources).collect(),
+        }
+    }
+}
+
+impl<Msg: Send + 'static> Sub<Msg> {
+    /// Send `msg()` every `every`, from a job on the app's [`Spawner`](crate::Spawner).
+    ///
+    /// The source is keyed by `id` and `every`, so changing the period restarts it.
+    pub fn interval<F>(id: impl Hash, every: Duration, msg: F) -> Self
+    where
+        F: Fn() -> Msg + Send + 'static,
+    {
+        Self::spawn(("interval", id, every), move |tx, token| {
+            let mut next = Instant::now() + every;
+            while token.sleep_until(next) {
+                if tx.send(msg()).is_err() {
+                    return;
+                }
+                next += every;
+            }
+        })
+    }
+
+    /// A custom source, keyed by `id`, running `source` as a job on the app's
+    /// [`Spawner`](crate::Spawner).
+    ///
+    /// `source` should send messages through the [`ChaiSender`] until the [`CancelToken`]
+    /// is cancelled, which happens once `id` is no longer in the app's subscriptions.
+    /// From then on, sends fail.
+    ///
+    /// The job holds on to its worker until then, so each running source takes up one of a
+    /// [`ThreadPool`](crate::ThreadPool)'s threads, and under
+    /// [`InlineSpawner`](crate::InlineSpawner) a source that waits to be cancelled never
+    /// returns.
+    pub fn spawn<F>(id: impl Hash, source: F) -> Self
+    where
+        F: FnOnce(ChaiSender<Msg>, CancelToken) + Send + 'static,
+    {
+        Self {
+            sources: vec![Source {
+                key: key_of(id),
+                start: Box::new(move |tx, token| {
+                    tx.clone().spawn(move || source(tx, token));
+                }),
+            }],
+        }
+    }
+}
+
+/// The sources currently running, by key.
+pub(crate) struct ActiveSubs {
+    running: std::collections::HashMap<u64, CancelToken>,
+}
+
+impl ActiveSubs {
+    pub(crate) fn new() -> Self {
+        Self {
+            running: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Start the sources in `sub` that aren't running yet, and cancel the ones it no longer lists.
+    pub(crate) fn diff<Msg>(&mut self, sub: Sub<Msg>, tx: &ChaiSender<Msg>) {
+        let mut running = std::collections::HashMap::with_capacity(sub.sources.len());
+
+        for source in sub.sources {
+            if running.contains_key(&source.key) {
+                continue;
+            }
+            let token = match self.running.remove(&source.key) {
+                Some(token) => token,
+                None => {
+                    let token = CancelToken::new();
+                    let tx = ChaiSender::clone(tx).with_token(token.clone());
+                    (source.start)(tx, token.clone());
+                    token
+                }
+            };
+            running.insert(source.key, token);
+        }
+
+        for token in self.running.values() {
+            token.cancel();
+        }
+        self.running = running;
+    }
+}
+
+impl Drop for ActiveSubs {
+    fn drop(&mut self) {
+        for token in self.running.values() {
+            token.cancel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex, mpsc};
+
+    use super::*;
+    use crate::InlineSpawner;
+
+    type Started = Arc<Mutex<Vec<(&'static str, CancelToken)>>>;
+
+    fn sources(ids: &[&'static str], started: &Started) -> Sub<()> {
+        Sub::batch(ids.iter().map(|&id| {
+            let started = Arc::clone(started);
+            Sub::spawn(id, move |_, token| {
+                started.lock().unwrap().push((id, token))
+            })
+        }))
+    }
+
+    #[test]
+    fn diff_starts_new_keys_and_cancels_missing_ones() {
+        let (tx, _rx) = mpsc::channel();
+        // inline, so a source has run by the time `diff` returns only if it went through here
+        let tx = ChaiSender::new(tx).with_spawner(Arc::new(InlineSpawner));
+        let started = Started::default();
+        let mut active = ActiveSubs::new();
+
+        active.diff(sources(&["a", "b"], &started), &tx);
+        active.diff(sources(&["b", "c", "c"], &started), &tx);
+
+        let states: Vec<_> = started
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, token)| (*id, token.is_cancelled()))
+            .collect();
+        assert_eq!(states, [("a", true), ("b", false), ("c", false)]);
+
+        drop(active);
+        assert!(
+            started
+                .lock()
+                .unwrap()
+                .iter()
+                .all(|(_, token)| token.is_cancelled())
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! One-shot messages scheduled by [`Cmd::after`](crate::Cmd::after) and [`Cmd::at`](crate::Cmd::at).
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+
+struct Timer<Msg> {
+    key: Option<u64>,
+    msg: Msg,
+}
+
+/// Every pending delivery, in deadline order.
+///
+/// The runtime checks it once per frame and asks egui to repaint when the next one is due, so
+/// any number of delays cost no threads and no polling. Keyed deliveries are also indexed by
+/// key, so cancelling one doesn't scan the rest.
+pub(crate) struct Timers<Msg> {
+    // ties at the same instant keep the order they were scheduled in
+    pending: BTreeMap<Slot, Timer<Msg>>,
+    by_key: HashMap<u64, Vec<Slot>>,
+    scheduled: u64,
+}
+
+/// Where a delivery sits in `pending`: its deadline, then its place in the scheduling order.
+type Slot = (Instant, u64);
+
+impl<Msg> Timers<Msg> {
+    pub(crate) fn new() -> Self {
+        Self {
+            pending: BTreeMap::new(),
+            by_key: HashMap::new(),
+            scheduled: 0,
+        }
+    }
+
+    pub(crate) fn schedule(&mut self, at: Instant, key: Option<u64>, msg: Msg) {
+        self.scheduled += 1;
+        let slot = (at, self.scheduled);
+        if let Some(key) = key {
+            self.by_key.entry(key).or_default().push(slot);
+        }
+        self.pending.insert(slot, Timer { key, msg });
+    }
+
+    /// Drop every delivery scheduled under `key`.
+    pub(crate) fn cancel(&mut self, key: u64) {
+        for slot in self.by_key.remove(&key).unwrap_or_default() {
+            self.pending.remove(&slot);
+        }
+    }
+
+    /// Take the messages due by `now`, earliest first.
+    pub(crate) fn pop_due(&mut self, now: Instant) -> Vec<Msg> {
+        let mut due = Vec::new();
+        while let Some(entry) = self.pending.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            let (slot, timer) = entry.remove_entry();
+            if let Some(key) = timer.key {
+                self.forget(key, slot);
+            }
+            due.push(timer.msg);
+        }
+        due
+    }
+
+    /// Drop a delivered `slot` from the index of `key`.
+    fn forget(&mut self, key: u64, slot: Slot) {
+        if let Some(slots) = self.by_key.get_mut(&key) {
+            slots.retain(|&s| s != slot);
+            if slots.is_empty() {
+                self.by_key.remove(&key);
+            }
+        }
+    }
+
+    /// When the next delivery is due, if any is pending.
+    pub(crate) fn next(&self) -> Option<Instant> {
+        self.pending.keys().next().map(|(at, _)| *at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn pops_due_messages_in_deadline_order() {
+        let start = Instant::now();
+        let mut timers = Timers::new();
+        timers.schedule(start + ms(30), None, "c");
+        timers.schedule(start + ms(10), None, "a");
+        timers.schedule(start + ms(20), None, "b");
+        timers.schedule(start + ms(10), None, "a2");
+
+        assert!(timers.pop_due(start).is_empty());
+        assert_eq!(timers.pop_due(start + ms(20)), ["a", "a2", "b"]);
+        assert_eq!(timers.next(), Some(start + ms(30)));
+        assert_eq!(timers.pop_due(start + ms(30)), ["c"]);
+        assert_eq!(timers.next(), None);
+    }
+
+    #[test]
+    fn cancel_drops_only_that_key() {
+        let start = Instant::now();
+        let mut timers = Timers::new();
+        timers.schedule(start + ms(10), Some(1), "one");
+        timers.schedule(start + ms(20), Some(1), "one again");
+        timers.schedule(start + ms(15), Some(2), "two");
+        timers.schedule(start + ms(5), None, "unkeyed");
+
+        timers.cancel(1);
+        assert_eq!(timers.pop_due(start + ms(30)), ["unkeyed", "two"]);
+    }
+
+    #[test]
+    fn replacing_a_key_delivers_only_the_new_message() {
+        let start = Instant::now();
+        let mut timers = Timers::new();
+        timers.schedule(start + ms(10), Some(1), "old");
+        // what the runtime does for a keyed command that replaces a running one
+        timers.cancel(1);
+        timers.schedule(start + ms(20), Some(1), "new");
+
+        assert!(timers.pop_due(start + ms(10)).is_empty());
+        assert_eq!(timers.pop_due(start + ms(20)), ["new"]);
+    }
+
+    #[test]
+    fn delivered_messages_leave_the_key_index() {
+        let start = Instant::now();
+        let mut timers = Timers::new();
+        timers.schedule(start + ms(10), Some(1), "first");
+        assert_eq!(timers.pop_due(start + ms(10)), ["first"]);
+        assert!(timers.by_key.is_empty());
+
+        // a later delivery under the same key is unaffected by the one already sent
+        timers.schedule(start + ms(20), Some(1), "second");
+        assert_eq!(timers.pop_due(start + ms(20)), ["second"]);
+    }
+}
@@ -0,0 +1,322 @@
+//! The app's window: lifecycle events turned into messages, and properties derived from the model.
+
+use eframe::egui;
+
+/// Something that happened to the app's window, see
+/// [`AppBuilder::window_events`](crate::AppBuilder::window_events).
+#[derive(Clone, Debug)]
+pub enum WindowEvent {
+    /// The user or the OS asked to close the window. Return [`Cmd::cancel_close`](crate::Cmd::cancel_close)
+    /// from `update` to keep it open.
+    CloseRequested,
+    /// The window got keyboard focus. Also sent once at startup if it starts focused.
+    FocusGained,
+    /// The window lost keyboard focus.
+    FocusLost,
+    /// The window's inner size changed, in points. Also sent once the size is first known.
+    Resized(egui::Vec2),
+    /// The window was minimized.
+    Minimized,
+    /// The window was un-minimized.
+    Restored,
+    /// Files were dropped onto the window.
+    FilesDropped(Vec<egui::DroppedFile>),
+}
+
+type EventFn<Msg> = Box<dyn Fn(WindowEvent) -> Option<Msg>>;
+
+/// Watches the viewport from frame to frame and reports what changed.
+pub(crate) struct WindowEvents<Msg> {
+    to_msg: EventFn<Msg>,
+    focused: Option<bool>,
+    size: Option<egui::Vec2>,
+    minimized: Option<bool>,
+}
+
+impl<Msg> WindowEvents<Msg> {
+    pub(crate) fn new<F>(to_msg: F) -> Self
+    where
+        F: Fn(WindowEvent) -> Option<Msg> + 'static,
+    {
+        Self {
+            to_msg: Box::new(to_msg),
+            focused: None,
+            size: None,
+            minimized: None,
+        }
+    }
+
+    /// Push the messages for this frame's events onto `msgs`.
+    pub(crate) fn poll(&mut self, ctx: &egui::Context, msgs: &mut Vec<Msg>) {
+        let (close, focused, size, minimized, dropped) = ctx.input(|input| {
+            let viewport = input.viewport();
+            (
+                viewport.close_requested(),
+                viewport.focused,
+                viewport.inner_rect.map(|rect| rect.size()),
+                viewport.minimized,
+                input.raw.dropped_files.clone(),
+            )
+        });
+
+        let mut events = Vec::new();
+        if focused.is_some() && focused != self.focused {
+            match focused {
+                Some(true) => events.push(WindowEvent::FocusGained),
+                // losing a focus we never saw isn't news
+                _ if self.focused.is_some() => events.push(WindowEvent::FocusLost),
+                _ => {}
+            }
+            self.focused = focused;
+        }
+        if let Some(size) = size
+            && self.size != Some(size)
+        {
+            events.push(WindowEvent::Resized(size));
+            self.size = Some(size);
+        }
+        if minimized.is_some() && minimized != self.minimized {
+            match minimized {
+                Some(true) => events.push(WindowEvent::Minimized),
+                _ if self.minimized.is_some() => events.push(WindowEvent::Restored),
+                _ => {}
+            }
+            self.minimized = minimized;
+        }
+        if !dropped.is_empty() {
+            events.push(WindowEvent::FilesDropped(dropped));
+        }
+        if close {
+            events.push(WindowEvent::CloseRequested);
+        }
+
+        msgs.extend(events.into_iter().filter_map(&self.to_msg));
+    }
+}
+
+/// Window properties derived from the model, see [`AppBuilder::window`](crate::AppBuilder::window).
+///
+/// Properties left unset are left alone, so the window keeps whatever eframe's options or the
+/// user gave it. That includes unsetting one that was set: a title the model stops returning
+/// stays on the window until a new one replaces it, rather than going back to the original.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WindowProps {
+    title: Option<String>,
+    min_size: Option<egui::Vec2>,
+    max_size: Option<egui::Vec2>,
+    decorations: Option<bool>,
+    resizable: Option<bool>,
+    always_on_top: Option<bool>,
+}
+
+impl WindowProps {
+    /// No properties set, so the window is left as it is.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The window's title, replacing the one the app was started with.
+    pub fn title(self, title: impl Into<String>) -> Self {
+        Self {
+            title: Some(title.into()),
+            ..self
+        }
+    }
+
+    /// The smallest the window's inner area may be resized to, in points.
+    pub fn min_size(self, size: impl Into<egui::Vec2>) -> Self {
+        Self {
+            min_size: Some(size.into()),
+            ..self
+        }
+    }
+
+    /// The largest the window's inner area may be resized to, in points.
+    pub fn max_size(self, size: impl Into<egui::Vec2>) -> Self {
+        Self {
+            max_size: Some(size.into()),
+            ..self
+        }
+    }
+
+    /// Whether the window has a title bar and borders.
+    pub fn decorations(self, decorations: bool) -> Self {
+        Self {
+            decorations: Some(decorations),
+            ..self
+        }
+    }
+
+    /// Whether the user can resize the window.
+    pub fn resizable(self, resizable: bool) -> Self {
+        Self {
+            resizable: Some(resizable),
+            ..self
+        }
+    }
+
+    /// Whether the window stays above other windows.
+    pub fn always_on_top(self, on_top: bool) -> Self {
+        Self {
+            always_on_top: Some(on_top),
+            ..self
+        }
+    }
+
+    /// The viewport commands that take the window from `old` to `self`.
+    fn changes(&self, old: &WindowProps) -> Vec<egui::ViewportCommand> {
+        use egui::ViewportCommand as Command;
+
+        fn changed<T: Clone + PartialEq>(new: &Option<T>, old: &Option<T>) -> Option<T> {
+            new.clone().filter(|_| new != old)
+        }
+
+        let mut commands = Vec::new();
+        commands.extend(changed(&self.title, &old.title).map(Command::Title));
+        commands.extend(changed(&self.min_size, &old.min_size).map(Command::MinInnerSize));
+        commands.extend(changed(&self.max_size, &old.max_size).map(Command::MaxInnerSize));
+        commands.extend(changed(&self.decorations, &old.decorations).map(Command::Decorations));
+        commands.extend(changed(&self.resizable, &old.resizable).map(Command::Resizable));
+        commands.extend(
+            changed(&self.always_on_top, &old.always_on_top).map(|on_top| {
+                Command::WindowLevel(match on_top {
+                    true => egui::WindowLevel::AlwaysOnTop,
+                    false => egui::WindowLevel::Normal,
+                })
+            }),
+        );
+        commands
+    }
+}
+
+type PropsFn<M> = Box<dyn Fn(&M) -> WindowProps>;
+
+/// Keeps the window in step with the model's [`WindowProps`].
+pub(crate) struct Window<M> {
+    props: PropsFn<M>,
+    applied: WindowProps,
+}
+
+impl<M> Window<M> {
+    pub(crate) fn new<F>(props: F) -> Self
+    where
+        F: Fn(&M) -> WindowProps + 'static,
+    {
+        Self {
+            props: Box::new(props),
+            applied: WindowProps::default(),
+        }
+    }
+
+    /// Send the commands for whatever changed since the last call.
+    pub(crate) fn sync(&mut self, model: &M, ctx: &egui::Context) {
+        let props = (self.props)(model);
+        if props == self.applied {
+            return;
+        }
+        for command in props.changes(&self.applied) {
+            ctx.send_viewport_cmd(command);
+        }
+        self.applied = props;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run one frame whose root viewport reports `info`, and return what `events` made of it.
+    fn frame(
+        events: &mut WindowEvents<WindowEvent>,
+        ctx: &egui::Context,
+        info: egui::ViewportInfo,
+    ) -> Vec<String> {
+        let mut raw = egui::RawInput::default();
+        raw.viewports.insert(egui::ViewportId::ROOT, info);
+        ctx.begin_pass(raw);
+        let mut msgs = Vec::new();
+        events.poll(ctx, &mut msgs);
+        let _ = ctx.end_pass();
+        msgs.iter().map(|event| format!("{event:?}")).collect()
+    }
+
+    fn focused(focused: bool) -> egui::ViewportInfo {
+        egui::ViewportInfo {
+            focused: Some(focused),
+            ..Default::default()
+        }
+    }
+
+    fn minimized(minimized: bool) -> egui::ViewportInfo {
+        egui::ViewportInfo {
+            minimized: Some(minimized),
+            ..Default::default()
+        }
+    }
+
+    fn sized(width: f32, height: f32) -> egui::ViewportInfo {
+        egui::ViewportInfo {
+            inner_rect: Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::vec2(width, height),
+            )),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn focus_lost_needs_a_focus_first() {
+        let ctx = egui::Context::default();
+        let mut events = WindowEvents::new(Some);
+
+        assert!(frame(&mut events, &ctx, focused(false)).is_empty());
+        assert_eq!(frame(&mut events, &ctx, focused(true)), ["FocusGained"]);
+        assert!(frame(&mut events, &ctx, focused(true)).is_empty());
+        assert_eq!(frame(&mut events, &ctx, focused(false)), ["FocusLost"]);
+    }
+
+    #[test]
+    fn resized_once_the_size_is_known_and_whenever_it_changes() {
+        let ctx = egui::Context::default();
+        let mut events = WindowEvents::new(Some);
+
+        assert!(frame(&mut events, &ctx, egui::ViewportInfo::default()).is_empty());
+        assert_eq!(
+            frame(&mut events, &ctx, sized(800.0, 600.0)),
+            ["Resized([800.0 600.0])"]
+        );
+        assert!(frame(&mut events, &ctx, sized(800.0, 600.0)).is_empty());
+        assert_eq!(
+            frame(&mut events, &ctx, sized(640.0, 480.0)),
+            ["Resized([640.0 480.0])"]
+        );
+    }
+
+    #[test]
+    fn minimized_and_restored_come_in_pairs() {
+        let ctx = egui::Context::default();
+        let mut events = WindowEvents::new(Some);
+
+        assert!(frame(&mut events, &ctx, minimized(false)).is_empty());
+        assert_eq!(frame(&mut events, &ctx, minimized(true)), ["Minimized"]);
+        assert!(frame(&mut events, &ctx, minimized(true)).is_empty());
+        assert_eq!(frame(&mut events, &ctx, minimized(false)), ["Restored"]);
+    }
+
+    #[test]
+    fn a_changed_prop_is_one_command() {
+        let old = WindowProps::new().title("tea").resizable(true);
+
+        let changes = old.clone().title("coffee").changes(&old);
+
+        assert_eq!(changes, [egui::ViewportCommand::Title("coffee".into())]);
+    }
+
+    #[test]
+    fn unchanged_or_unset_props_are_no_commands() {
+        let old = WindowProps::new().title("tea").always_on_top(true);
+
+        assert!(old.clone().changes(&old).is_empty());
+        assert!(WindowProps::new().changes(&old).is_empty());
+    }
+}